@@ -0,0 +1,153 @@
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    process::{Command, Stdio},
+};
+
+use crate::{parser::Direction, simulation::Simulation, warehouse::Warehouse};
+
+const HELP: &str = "arrows or <^>v move, u undo, r redo, q quit";
+
+#[derive(Debug)]
+enum Key {
+    Move(Direction),
+    Undo,
+    Redo,
+    Quit,
+    Other,
+}
+
+/// Switches the terminal into raw mode, so single key presses can be read
+/// without echo. Dropping it restores the previous settings, also when
+/// unwinding from a panic.
+struct RawMode {
+    /// settings before raw mode, as printed by `stty -g`
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        if !io::stdin().is_terminal() {
+            return Err(io::Error::other("The game needs a terminal as input"));
+        }
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Err(error) = stty(&[&self.saved]) {
+            eprintln!("{}, run `stty sane` to reset the terminal", error);
+        }
+    }
+}
+
+/// runs stty on the terminal of stdin and returns what it printed
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|error| io::Error::new(error.kind(), format!("Could not run stty: {}", error)))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::other(format!(
+            "stty {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Lets the user move the robot with the keyboard until they quit
+pub(crate) fn run(warehouse: &mut Warehouse) -> io::Result<()> {
+    let _raw_mode = RawMode::enable()?;
+    let mut simulation = Simulation::new(warehouse).with_history();
+    let mut stdout = io::stdout().lock();
+    let mut bytes = io::stdin().lock().bytes();
+
+    render(&mut stdout, &simulation)?;
+    while let Some(key) = next_key(&mut bytes)? {
+        match key {
+            Key::Move(direction) => simulation.move_robot(&direction),
+            Key::Undo => {
+                simulation.undo();
+            }
+            Key::Redo => {
+                simulation.redo();
+            }
+            Key::Quit => break,
+            Key::Other => continue,
+        }
+        render(&mut stdout, &simulation)?;
+    }
+
+    Ok(())
+}
+
+/// clears the screen and draws the warehouse, lines end with \r\n in raw mode
+fn render(out: &mut impl Write, simulation: &Simulation) -> io::Result<()> {
+    write!(out, "\x1b[H\x1b[2J")?;
+    for line in simulation.warehouse.to_string().lines() {
+        write!(out, "{}\r\n", line)?;
+    }
+    write!(
+        out,
+        "GPS: {} Moves: {}\r\n{}\r\n",
+        simulation.warehouse.gps(),
+        simulation.log.len(),
+        HELP
+    )?;
+    out.flush()
+}
+
+/// Decodes the next key press, arrow keys arrive as `ESC [ A` to `ESC [ D`.
+/// Returns None at the end of the input.
+fn next_key(bytes: &mut impl Iterator<Item = io::Result<u8>>) -> io::Result<Option<Key>> {
+    let Some(byte) = bytes.next().transpose()? else {
+        return Ok(None);
+    };
+
+    let key = match byte {
+        b'\x1b' => match bytes.next().transpose()? {
+            Some(b'[' | b'O') => match bytes.next().transpose()? {
+                Some(b'A') => Key::Move(Direction::Up),
+                Some(b'B') => Key::Move(Direction::Down),
+                Some(b'C') => Key::Move(Direction::Right),
+                Some(b'D') => Key::Move(Direction::Left),
+                _ => Key::Other,
+            },
+            _ => Key::Other,
+        },
+        b'u' => Key::Undo,
+        b'r' => Key::Redo,
+        // ctrl-c does not send a signal in raw mode
+        b'q' | b'\x03' => Key::Quit,
+        c => Direction::from_char(char::from(c)).map_or(Key::Other, Key::Move),
+    };
+
+    Ok(Some(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_keys() -> io::Result<()> {
+        let mut bytes = b"\x1b[A\x1bOD<vuxq"[..].bytes();
+        let mut keys = Vec::new();
+        while let Some(key) = next_key(&mut bytes)? {
+            keys.push(format!("{:?}", key));
+        }
+
+        assert_eq!(
+            vec!["Move(^)", "Move(<)", "Move(<)", "Move(v)", "Undo", "Other", "Quit"],
+            keys
+        );
+
+        Ok(())
+    }
+}
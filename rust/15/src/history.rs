@@ -0,0 +1,72 @@
+use crate::{
+    simulation::LoggedMove,
+    warehouse::{Field, Point, Warehouse},
+};
+
+/// Single field overwritten while moving the robot
+pub(crate) struct FieldChange {
+    pub(crate) point: Point,
+    pub(crate) before: Field,
+    pub(crate) after: Field,
+}
+
+/// All field changes of one move of one or more robots, in the order they were made
+pub(crate) struct MoveCommand {
+    pub(crate) robots_before: Vec<Point>,
+    pub(crate) robots_after: Vec<Point>,
+    pub(crate) changes: Vec<FieldChange>,
+    /// length of the move log before this move
+    pub(crate) log_length: usize,
+    /// Log entries of this move and the blocked moves after it, kept while
+    /// the move is undone and put back into the log when it is redone
+    pub(crate) log: Vec<LoggedMove>,
+}
+
+impl MoveCommand {
+    pub(crate) fn undo(&self, warehouse: &mut Warehouse) {
+        for change in self.changes.iter().rev() {
+            warehouse[&change.point] = change.before.clone();
+        }
+    }
+
+    pub(crate) fn redo(&self, warehouse: &mut Warehouse) {
+        for change in &self.changes {
+            warehouse[&change.point] = change.after.clone();
+        }
+    }
+}
+
+/// Moves that can be undone and moves that were undone and can be redone.
+///
+/// Only moves that changed the warehouse are recorded, so undo never
+/// takes back a move into a wall.
+#[derive(Default)]
+pub(crate) struct History {
+    done: Vec<MoveCommand>,
+    undone: Vec<MoveCommand>,
+}
+
+impl History {
+    /// a new move discards the moves that could be redone
+    pub(crate) fn record(&mut self, command: MoveCommand) {
+        self.done.push(command);
+        self.undone.clear();
+    }
+
+    /// a move that did not change the warehouse still ends redoing
+    pub(crate) fn discard_undone(&mut self) {
+        self.undone.clear();
+    }
+
+    pub(crate) fn undo(&mut self) -> Option<&mut MoveCommand> {
+        let command = self.done.pop()?;
+        self.undone.push(command);
+        self.undone.last_mut()
+    }
+
+    pub(crate) fn redo(&mut self) -> Option<&mut MoveCommand> {
+        let command = self.undone.pop()?;
+        self.done.push(command);
+        self.done.last_mut()
+    }
+}
@@ -1,76 +1,263 @@
-use std::env;
+use std::{env, fs, io, iter, process, time::Instant};
 
-use parser::parse_file;
-use simulation::Simulation;
-use warehouse::{Field, Warehouse};
+use parser::{parse_file, parse_warehouse, Direction, ParseError};
+use replay::Replay;
+use simulation::{RobotContact, Schedule, Simulation};
+use solver::Goal;
+use summary::Summary;
+use warehouse::Warehouse;
 
+pub mod game;
+pub mod history;
 pub mod parser;
+pub mod replay;
 pub mod simulation;
+pub mod solver;
+pub mod summary;
 pub mod warehouse;
 
+const CAST_FRAMES_PER_SECOND: f64 = 10.0;
+const DEFAULT_MAX_STATES: usize = 250_000;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let path = args.get(1).expect("Please provide an input file path");
-    part_one(path);
-    part_two(path);
+    // part 1, part 2 or any scale as <horizontal>x<vertical>, e.g. 3x2
+    let scale = match args.get(3).map(String::as_str) {
+        None | Some("1") => (1, 1),
+        Some("2") => (2, 1),
+        Some(scale) => scale
+            .split_once('x')
+            .and_then(|(h, v)| Some((h.parse().ok()?, v.parse().ok()?)))
+            .filter(|&(h, v)| h > 0 && v > 0)
+            .unwrap_or_else(|| panic!("Unknown scale {}, expected 1, 2 or 3x2", scale)),
+    };
+
+    match args.get(2).map(String::as_str) {
+        // replay <scale> [turns|simultaneous] [push|block], steps through the moves
+        // with commands read from stdin
+        Some("replay") => record(path, scale, schedule(args.get(4)), contact(args.get(5)))
+            .run_interactive(io::stdin().lock(), &mut io::stdout())
+            .expect("Could not run the replay"),
+        // cast <scale> <output> [turns|simultaneous] [push|block], writes an asciicast
+        // v2 file for `asciinema play`
+        Some("cast") => {
+            let output = args.get(4).expect("Please provide an output file path");
+            let replay = record(path, scale, schedule(args.get(5)), contact(args.get(6)));
+            let cast = replay.to_asciicast(CAST_FRAMES_PER_SECOND);
+            fs::write(output, cast).expect("Could not write the cast file");
+        }
+        // play [scale], moves the robot with <^>v read from stdin, u undoes and r redoes
+        Some("play") => play(path, scale),
+        // game [scale], moves the robot with single key presses in the terminal
+        Some("game") => {
+            let (mut warehouse, _) = load(path, scale);
+            game::run(&mut warehouse).expect("Could not run the game");
+        }
+        // solve <scale> <min|max|target> [max states], prints the moves reaching the
+        // lowest or highest GPS or the box layout of the target file
+        Some("solve") => {
+            let (warehouse, _) = load(path, scale);
+            let goal = match args.get(4).map(String::as_str) {
+                Some("min") => Goal::MinGps,
+                Some("max") => Goal::MaxGps,
+                Some(target) => Goal::layout(&load_target(target, scale)),
+                None => panic!("Please provide min, max or a target file"),
+            };
+            let max_states = args.get(5).map_or(DEFAULT_MAX_STATES, |n| {
+                n.parse().expect("Invalid maximum number of states")
+            });
+            match solver::solve(&warehouse, &goal, max_states) {
+                Ok(solution) => {
+                    println!("{}", solution.move_string());
+                    eprintln!("{} moves, GPS: {}", solution.moves.len(), solution.gps);
+                }
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+        // robots <scale> [turns|simultaneous] [push|block], runs every move section
+        // with its own robot and prints how often each robot moved
+        Some("robots") => {
+            let (mut warehouse, moves) = load(path, scale);
+            let mut simulation = Simulation::new(&mut warehouse);
+            simulation.robot_contact = contact(args.get(5));
+            simulation.run_robots(&moves, schedule(args.get(4)));
+
+            println!(
+                "{}GPS: {}",
+                simulation.warehouse,
+                simulation.warehouse.gps()
+            );
+            for robot in 0..simulation.robot_positions.len() {
+                let (moved, blocked) = simulation.log.iter().filter(|m| m.robot == robot).fold(
+                    (0, 0),
+                    |(moved, blocked), m| {
+                        if m.moved {
+                            (moved + 1, blocked)
+                        } else {
+                            (moved, blocked + 1)
+                        }
+                    },
+                );
+                println!("Robot {}: {} moved, {} blocked", robot, moved, blocked);
+            }
+        }
+        // verbose, prints the warehouse after every move
+        Some("verbose") => {
+            part_one(path, true).unwrap_or_else(|error| exit_with(path, error));
+            part_two(path, true).unwrap_or_else(|error| exit_with(path, error));
+        }
+        // batch [scale] [more files...], prints one summary per file and reports
+        // bad files instead of stopping
+        Some("batch") => {
+            for path in iter::once(path).chain(args.iter().skip(4)) {
+                match parse_file(path) {
+                    Ok(input) => {
+                        let (horizontal, vertical) = scale;
+                        let warehouse = Warehouse::scaled(&input.warehouse, horizontal, vertical);
+                        println!("{}: {}", path, simulate(warehouse, &input.moves, false));
+                    }
+                    Err(error) => eprintln!("{}: {}", path, error),
+                }
+            }
+        }
+        Some(mode) => panic!("Unknown mode {}", mode),
+        None => {
+            let part_one = part_one(path, false).unwrap_or_else(|error| exit_with(path, error));
+            println!("Part one: {}", part_one);
+            let part_two = part_two(path, false).unwrap_or_else(|error| exit_with(path, error));
+            println!("Part two: {}", part_two);
+        }
+    }
 }
 
-fn part_one(path: &str) -> usize {
-    let input = parse_file(path).unwrap();
-    let mut warehouse = input.warehouse;
+/// parses the input file and scales the warehouse
+fn load(path: &str, scale: (usize, usize)) -> (Warehouse, Vec<Vec<Direction>>) {
+    let input = parse_file(path).unwrap_or_else(|error| exit_with(path, error));
+    match scale {
+        (1, 1) => (input.warehouse, input.moves),
+        (horizontal, vertical) => (
+            Warehouse::scaled(&input.warehouse, horizontal, vertical),
+            input.moves,
+        ),
+    }
+}
 
-    let robot_start = warehouse
-        .iter_fields()
-        .find(|(_, f)| matches!(f, Field::Robot))
-        .expect("Warehouse contains a robot field")
-        .0;
+/// Parses the warehouse of a target layout. The robot does not matter for a
+/// layout, so the file may contain any number of robots and no moves.
+fn load_target(path: &str, scale: (usize, usize)) -> Warehouse {
+    let warehouse = fs::read_to_string(path)
+        .map_err(ParseError::from)
+        .and_then(|input| {
+            let map: Vec<&str> = input.lines().take_while(|l| !l.is_empty()).collect();
+            parse_warehouse(&map.join("\n"))
+        })
+        .unwrap_or_else(|error| exit_with(path, error));
+    Warehouse::scaled(&warehouse, scale.0, scale.1)
+}
 
-    println!("{}", warehouse);
-    println!("{:?}", input.moves);
+/// reports a bad input file without a panic message
+fn exit_with(path: &str, error: ParseError) -> ! {
+    eprintln!("{}: {}", path, error);
+    process::exit(1)
+}
 
-    assert!(matches!(warehouse[&robot_start], Field::Robot));
+fn play(path: &str, scale: (usize, usize)) {
+    let (mut warehouse, _) = load(path, scale);
+    let mut simulation = Simulation::new(&mut warehouse).with_history();
 
-    let mut simulation = Simulation {
-        warehouse: &mut warehouse,
-        robot_position: robot_start,
-    };
+    println!(
+        "{}GPS: {}",
+        simulation.warehouse,
+        simulation.warehouse.gps()
+    );
+    for line in io::stdin().lines() {
+        for c in line.expect("Could not read from stdin").chars() {
+            match (c, Direction::from_char(c)) {
+                (_, Some(direction)) => simulation.move_robot(&direction),
+                ('u', _) => {
+                    simulation.undo();
+                }
+                ('r', _) => {
+                    simulation.redo();
+                }
+                _ => {}
+            }
+        }
+        println!(
+            "{}GPS: {}",
+            simulation.warehouse,
+            simulation.warehouse.gps()
+        );
+    }
+}
 
-    for direction in input.moves {
-        println!("Move {}", direction);
-        simulation.move_robot(&direction);
-        println!("{}", simulation.warehouse);
-        println!("GPS: {}", simulation.warehouse.gps());
+fn schedule(arg: Option<&String>) -> Schedule {
+    match arg.map(String::as_str) {
+        None | Some("turns") => Schedule::Turns,
+        Some("simultaneous") => Schedule::Simultaneous,
+        Some(schedule) => panic!("Unknown schedule {}", schedule),
     }
-    simulation.warehouse.gps()
 }
 
-fn part_two(path: &str) -> usize {
-    let input = parse_file(path).unwrap();
-    let mut warehouse = Warehouse::scaled_up(&input.warehouse);
+fn contact(arg: Option<&String>) -> RobotContact {
+    match arg.map(String::as_str) {
+        None | Some("push") => RobotContact::Push,
+        Some("block") => RobotContact::Block,
+        Some(contact) => panic!("Unknown robot contact {}", contact),
+    }
+}
 
-    let robot_start = warehouse
-        .iter_fields()
-        .find(|(_, f)| matches!(f, Field::Robot))
-        .expect("Warehouse contains a robot field")
-        .0;
+/// runs all moves and returns the replay of the simulation
+fn record(
+    path: &str,
+    scale: (usize, usize),
+    schedule: Schedule,
+    robot_contact: RobotContact,
+) -> Replay {
+    let (initial, moves) = load(path, scale);
 
-    println!("{}", warehouse);
-    println!("{:?}", input.moves);
+    let mut warehouse = initial.clone();
+    let mut simulation = Simulation::new(&mut warehouse);
+    simulation.robot_contact = robot_contact;
+    simulation.run_robots(&moves, schedule);
 
-    assert!(matches!(warehouse[&robot_start], Field::Robot));
+    Replay::new(&initial, &simulation)
+}
 
-    let mut simulation = Simulation {
-        warehouse: &mut warehouse,
-        robot_position: robot_start,
-    };
+fn part_one(path: &str, verbose: bool) -> Result<Summary, ParseError> {
+    let input = parse_file(path)?;
+    Ok(simulate(input.warehouse, &input.moves, verbose))
+}
 
-    for direction in input.moves {
-        println!("Move {}", direction);
-        simulation.move_robot(&direction);
+fn part_two(path: &str, verbose: bool) -> Result<Summary, ParseError> {
+    let input = parse_file(path)?;
+    Ok(simulate(
+        Warehouse::scaled_up(&input.warehouse),
+        &input.moves,
+        verbose,
+    ))
+}
+
+/// Moves the robots in turns, printing the warehouse after every move if verbose
+fn simulate(mut warehouse: Warehouse, moves: &[Vec<Direction>], verbose: bool) -> Summary {
+    if !verbose {
+        return summary::run_quiet(&mut warehouse, moves);
+    }
+
+    println!("{}", warehouse);
+    println!("{:?}", moves);
+
+    let start = Instant::now();
+    let mut simulation = Simulation::new(&mut warehouse);
+    simulation.run_robots_with(moves, Schedule::Turns, |simulation, logged| {
+        for logged in logged {
+            println!("Move {}", logged.direction);
+        }
         println!("{}", simulation.warehouse);
         println!("GPS: {}", simulation.warehouse.gps());
-    }
-    simulation.warehouse.gps()
+    });
+    Summary::new(&simulation, start.elapsed())
 }
 
 #[cfg(test)]
@@ -78,17 +265,20 @@ mod tests {
     use super::*;
 
     #[test]
-    fn part_one_small() {
-        assert_eq!(2028, part_one("small.txt"));
+    fn part_one_small() -> Result<(), ParseError> {
+        assert_eq!(2028, part_one("small.txt", false)?.gps);
+        Ok(())
     }
 
     #[test]
-    fn part_one_sample() {
-        assert_eq!(10092, part_one("sample.txt"));
+    fn part_one_sample() -> Result<(), ParseError> {
+        assert_eq!(10092, part_one("sample.txt", false)?.gps);
+        Ok(())
     }
 
     #[test]
-    fn part_two_sample() {
-        assert_eq!(9021, part_two("sample.txt"));
+    fn part_two_sample() -> Result<(), ParseError> {
+        assert_eq!(9021, part_two("sample.txt", false)?.gps);
+        Ok(())
     }
 }
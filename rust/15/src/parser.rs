@@ -1,42 +1,184 @@
 use std::{
+    error::Error,
     fmt::{Debug, Display},
     fs, io,
 };
 
 use crate::warehouse::{Field, Warehouse};
 
-pub(crate) fn parse_file(path: &str) -> Result<Input, io::Error> {
+#[derive(Debug)]
+pub(crate) enum ParseError {
+    Io(io::Error),
+    EmptyWarehouse,
+    /// line and column start at 1
+    InvalidField {
+        line: usize,
+        column: usize,
+        char: char,
+    },
+    InvalidMove {
+        line: usize,
+        column: usize,
+        char: char,
+    },
+    /// a row with a different length than the first one
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// a field at the edge of the warehouse that is not a wall
+    OpenBorder {
+        line: usize,
+        column: usize,
+    },
+    /// every move section needs its own robot, a warehouse without moves one robot
+    RobotCount {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(error) => write!(f, "{}", error),
+            ParseError::EmptyWarehouse => write!(f, "The warehouse is empty"),
+            ParseError::InvalidField { line, column, char } => {
+                write!(
+                    f,
+                    "Line {}, column {}: invalid field '{}'",
+                    line, column, char
+                )
+            }
+            ParseError::InvalidMove { line, column, char } => {
+                write!(
+                    f,
+                    "Line {}, column {}: invalid move '{}'",
+                    line, column, char
+                )
+            }
+            ParseError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {}: row has {} fields, expected {}",
+                line, found, expected
+            ),
+            ParseError::OpenBorder { line, column } => write!(
+                f,
+                "Line {}, column {}: the border of the warehouse must be a wall",
+                line, column
+            ),
+            ParseError::RobotCount { expected, found } => {
+                write!(f, "Found {} robots, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+pub(crate) fn parse_file(path: &str) -> Result<Input, ParseError> {
     let input = fs::read_to_string(path)?;
-    let mut rows: Vec<Vec<Field>> = Vec::new();
-    let mut moves: Vec<Direction> = Vec::new();
+    parse_str(&input)
+}
 
-    let mut reading_warehouse = true;
+/// Parses the warehouse and the move sections after it, every empty line
+/// starts the moves of the next robot
+pub(crate) fn parse_str(input: &str) -> Result<Input, ParseError> {
+    let map: Vec<&str> = input.lines().take_while(|l| !l.is_empty()).collect();
+    let warehouse = parse_warehouse(&map.join("\n"))?;
+    let mut moves: Vec<Vec<Direction>> = Vec::new();
 
-    for line in input.lines() {
+    for (index, line) in input.lines().enumerate().skip(map.len()) {
         if line.is_empty() {
-            reading_warehouse = false;
-        } else if reading_warehouse {
-            let row = line.chars().map(Field::from).collect();
-            rows.push(row);
-        } else {
-            moves.extend(line.chars().map(|c| match c {
-                '<' => Direction::Left,
-                '^' => Direction::Up,
-                '>' => Direction::Right,
-                'v' => Direction::Down,
-                _ => panic!("Invalid move char"),
-            }));
+            if moves.last().is_none_or(|m| !m.is_empty()) {
+                moves.push(Vec::new());
+            }
+            continue;
+        }
+        let section = moves.last_mut().expect("Started a move section");
+        for (column, c) in line.chars().enumerate() {
+            let direction = Direction::from_char(c).ok_or(ParseError::InvalidMove {
+                line: index + 1,
+                column: column + 1,
+                char: c,
+            })?;
+            section.push(direction);
         }
     }
+    if moves.last().is_some_and(|m| m.is_empty()) {
+        moves.pop();
+    }
 
-    let warehouse = Warehouse::new(rows);
+    let expected = moves.len().max(1);
+    let found = warehouse.robot_positions().len();
+    if found != expected {
+        return Err(ParseError::RobotCount { expected, found });
+    }
 
     Ok(Input { warehouse, moves })
 }
 
+/// Parses a rectangular warehouse enclosed by walls, with any number of robots
+pub(crate) fn parse_warehouse(map: &str) -> Result<Warehouse, ParseError> {
+    let mut rows: Vec<Vec<Field>> = Vec::new();
+    for (index, line) in map.lines().enumerate() {
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(column, c)| {
+                Field::from_char(c).ok_or(ParseError::InvalidField {
+                    line: index + 1,
+                    column: column + 1,
+                    char: c,
+                })
+            })
+            .collect::<Result<Vec<Field>, ParseError>>()?;
+        if let Some(first) = rows.first() {
+            if row.len() != first.len() {
+                return Err(ParseError::RaggedRow {
+                    line: index + 1,
+                    expected: first.len(),
+                    found: row.len(),
+                });
+            }
+        }
+        rows.push(row);
+    }
+    if rows.first().is_none_or(|row| row.is_empty()) {
+        return Err(ParseError::EmptyWarehouse);
+    }
+
+    let (height, width) = (rows.len(), rows[0].len());
+    for (row, fields) in rows.iter().enumerate() {
+        for (col, field) in fields.iter().enumerate() {
+            let at_border = row == 0 || row == height - 1 || col == 0 || col == width - 1;
+            if at_border && !matches!(field, Field::Wall) {
+                return Err(ParseError::OpenBorder {
+                    line: row + 1,
+                    column: col + 1,
+                });
+            }
+        }
+    }
+
+    Ok(Warehouse::new(rows))
+}
+
 pub(crate) struct Input {
     pub(crate) warehouse: Warehouse,
-    pub(crate) moves: Vec<Direction>,
+    /// one list of moves per robot, in reading order of the robots
+    pub(crate) moves: Vec<Vec<Direction>>,
 }
 
 #[derive(Clone)]
@@ -47,6 +189,18 @@ pub(crate) enum Direction {
     Down,
 }
 
+impl Direction {
+    pub(crate) fn from_char(c: char) -> Option<Self> {
+        match c {
+            '<' => Some(Direction::Left),
+            '^' => Some(Direction::Up),
+            '>' => Some(Direction::Right),
+            'v' => Some(Direction::Down),
+            _ => None,
+        }
+    }
+}
+
 impl Display for Direction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -68,3 +222,50 @@ impl Debug for Direction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_move_sections() -> Result<(), ParseError> {
+        let input = parse_str("#####\n#@.@#\n#####\n\n<>\n^\n\n\nv\n")?;
+
+        assert_eq!(2, input.moves.len());
+        assert_eq!("[<, >, ^]", format!("{:?}", input.moves[0]));
+        assert_eq!(1, input.moves[1].len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_invalid_inputs() {
+        let error = |input: &str| parse_str(input).err().map(|e| e.to_string());
+
+        assert_eq!(Some("The warehouse is empty".to_string()), error("\n<>"));
+        assert_eq!(
+            Some("Line 2, column 3: invalid field 'x'".to_string()),
+            error("####\n#@x#\n####")
+        );
+        assert_eq!(
+            Some("Line 5, column 2: invalid move 'x'".to_string()),
+            error("####\n#@.#\n####\n\n<x>")
+        );
+        assert_eq!(
+            Some("Line 2: row has 5 fields, expected 4".to_string()),
+            error("####\n#@..#\n####")
+        );
+        assert_eq!(
+            Some("Line 2, column 4: the border of the warehouse must be a wall".to_string()),
+            error("####\n#@..\n####")
+        );
+        assert_eq!(
+            Some("Found 2 robots, expected 1".to_string()),
+            error("#####\n#@.@#\n#####\n\n<>")
+        );
+        assert_eq!(
+            Some("Found 0 robots, expected 1".to_string()),
+            error("####\n#..#\n####")
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    simulation::{LoggedMove, RobotContact, Simulation},
+    warehouse::{Point, Warehouse},
+};
+
+/// every KEYFRAME_INTERVAL moves a copy of the warehouse is stored,
+/// so jumping to a frame replays at most that many moves
+const KEYFRAME_INTERVAL: usize = 100;
+
+const HELP: &str = "\
+Commands:
+  n, next [k]     step k moves forward (default 1)
+  p, prev [k]     step k moves backward (default 1)
+  g, goto <n>     jump to frame n, frame 0 is the initial warehouse
+  q, quit         exit the replay";
+
+/// Move log of a simulation that can be stepped through in both directions.
+///
+/// Frame 0 is the initial warehouse, frame n the warehouse after the nth tick
+/// of the simulation. Moves made at the same time are replayed together, with
+/// the robot contact of the simulation, so every frame matches the run.
+pub(crate) struct Replay {
+    /// the moves of every tick
+    frames: Vec<Vec<LoggedMove>>,
+    robot_contact: RobotContact,
+    /// warehouses and robot positions, robots change their reading order while moving
+    keyframes: Vec<(Warehouse, Vec<Point>)>,
+    frame: usize,
+    current: Warehouse,
+    robot_positions: Vec<Point>,
+}
+
+impl Replay {
+    /// replays the log of a simulation that started with the initial warehouse
+    pub(crate) fn new(initial: &Warehouse, simulation: &Simulation) -> Self {
+        let robot_positions = initial.robot_positions();
+        let mut replay = Replay {
+            frames: simulation
+                .log
+                .chunk_by(|a, b| a.tick == b.tick)
+                .map(<[LoggedMove]>::to_vec)
+                .collect(),
+            robot_contact: simulation.robot_contact,
+            keyframes: vec![(initial.clone(), robot_positions.clone())],
+            frame: 0,
+            current: initial.clone(),
+            robot_positions,
+        };
+        while replay.step_forward() {
+            if replay.frame.is_multiple_of(KEYFRAME_INTERVAL) {
+                replay
+                    .keyframes
+                    .push((replay.current.clone(), replay.robot_positions.clone()));
+            }
+        }
+        (replay.current, replay.robot_positions) = replay.keyframes[0].clone();
+        replay.frame = 0;
+
+        replay
+    }
+
+    pub(crate) fn frame_count(&self) -> usize {
+        self.frames.len() + 1
+    }
+
+    pub(crate) fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub(crate) fn warehouse(&self) -> &Warehouse {
+        &self.current
+    }
+
+    /// moves that led to the current frame, empty for the first frame
+    pub(crate) fn last_moves(&self) -> &[LoggedMove] {
+        self.frame
+            .checked_sub(1)
+            .map_or(&[], |index| &self.frames[index])
+    }
+
+    /// returns false if already at the last frame
+    pub(crate) fn step_forward(&mut self) -> bool {
+        let Some(logged) = self.frames.get(self.frame) else {
+            return false;
+        };
+        let mut moves = vec![None; self.robot_positions.len()];
+        for logged in logged {
+            moves[logged.robot] = Some(logged.direction.clone());
+        }
+        let mut simulation =
+            Simulation::with_robot_positions(&mut self.current, self.robot_positions.clone());
+        simulation.robot_contact = self.robot_contact;
+        simulation.move_robots_simultaneously(&moves);
+        self.robot_positions = simulation.robot_positions;
+        self.frame += 1;
+        true
+    }
+
+    /// returns false if already at the first frame
+    pub(crate) fn step_back(&mut self) -> bool {
+        if self.frame == 0 {
+            return false;
+        }
+        self.goto(self.frame - 1);
+        true
+    }
+
+    /// jumps to the frame, or the last one if frame is out of range
+    pub(crate) fn goto(&mut self, frame: usize) {
+        let frame = frame.min(self.frames.len());
+        if frame < self.frame || frame / KEYFRAME_INTERVAL > self.frame / KEYFRAME_INTERVAL {
+            let keyframe = frame / KEYFRAME_INTERVAL;
+            (self.current, self.robot_positions) = self.keyframes[keyframe].clone();
+            self.frame = keyframe * KEYFRAME_INTERVAL;
+        }
+        while self.frame < frame {
+            self.step_forward();
+        }
+    }
+
+    /// Exports all frames as an asciicast v2 recording, playable with `asciinema play`
+    pub(crate) fn to_asciicast(&self, frames_per_second: f64) -> String {
+        let mut replay = Replay {
+            frames: self.frames.clone(),
+            robot_contact: self.robot_contact,
+            keyframes: self.keyframes.clone(),
+            frame: 0,
+            current: self.keyframes[0].0.clone(),
+            robot_positions: self.keyframes[0].1.clone(),
+        };
+        // the header comes first, but the size depends on the longest status line
+        let mut events = String::new();
+        let (mut width, mut height) = (0, 0);
+        loop {
+            let time = replay.frame as f64 / frames_per_second;
+            let mut screen = String::from("\x1b[H\x1b[2J");
+            let frame = replay.to_string();
+            for line in frame.lines() {
+                width = width.max(line.chars().count());
+                screen.push_str(line);
+                screen.push_str("\r\n");
+            }
+            // one more line for the cursor after the last line break
+            height = height.max(frame.lines().count() + 1);
+            writeln!(events, "[{:.3}, \"o\", {}]", time, json_string(&screen))
+                .expect("Writing to a string does not fail");
+            if !replay.step_forward() {
+                break;
+            }
+        }
+
+        format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}\n{}",
+            width, height, events
+        )
+    }
+
+    /// Reads commands line by line and prints the frame after each of them
+    pub(crate) fn run_interactive(
+        &mut self,
+        input: impl BufRead,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(output, "{}", HELP)?;
+        write!(output, "{}", self)?;
+
+        for line in input.lines() {
+            let line = line?;
+            let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let count = argument.trim().parse::<usize>().ok();
+            match (command, count) {
+                ("n" | "next", _) => {
+                    for _ in 0..count.unwrap_or(1) {
+                        self.step_forward();
+                    }
+                }
+                ("p" | "prev", _) => {
+                    for _ in 0..count.unwrap_or(1) {
+                        self.step_back();
+                    }
+                }
+                ("g" | "goto", Some(frame)) => self.goto(frame),
+                ("q" | "quit", _) => break,
+                ("", _) => continue,
+                _ => {
+                    writeln!(output, "Unknown command '{}'\n{}", line.trim(), HELP)?;
+                    continue;
+                }
+            }
+            write!(output, "{}", self)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.warehouse())?;
+        write!(f, "Frame {}/{}", self.frame(), self.frame_count() - 1)?;
+        match self.last_moves() {
+            [] => {}
+            [logged] => {
+                write!(f, " Move {}", logged.direction)?;
+                if !logged.moved {
+                    write!(f, " (blocked)")?;
+                }
+            }
+            moves => {
+                write!(f, " Moves")?;
+                for logged in moves {
+                    write!(f, " {}:{}", logged.robot, logged.direction)?;
+                    if !logged.moved {
+                        write!(f, " (blocked)")?;
+                    }
+                }
+            }
+        }
+        writeln!(f, " GPS: {}", self.warehouse().gps())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            c if c.is_control() => {
+                write!(json, "\\u{:04x}", c as u32).expect("Writing to a string does not fail")
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::{parse_warehouse, Direction},
+        simulation::Schedule,
+    };
+
+    use super::*;
+
+    /// runs the moves on the map and returns the initial warehouse and the replay
+    fn replay(map: &str, moves: &str) -> (Warehouse, Replay) {
+        let initial = parse_warehouse(map).expect("Valid warehouse");
+        let mut warehouse = initial.clone();
+        let mut simulation = Simulation::new(&mut warehouse);
+        for c in moves.chars() {
+            simulation.move_robot(&Direction::from_char(c).expect("Valid move"));
+        }
+        let replay = Replay::new(&initial, &simulation);
+
+        (initial, replay)
+    }
+
+    #[test]
+    fn steps_forward_and_back() {
+        let (initial, mut replay) = replay("#####\n#@O.#\n#####", ">><");
+
+        assert!(replay.step_forward());
+        assert_eq!("#####\n#.@O#\n#####\n", replay.warehouse().to_string());
+        assert!(replay.step_forward());
+        assert!(replay.last_moves().iter().all(|m| !m.moved));
+        assert!(replay.step_forward());
+        assert!(!replay.step_forward());
+        assert_eq!("#####\n#@.O#\n#####\n", replay.warehouse().to_string());
+
+        assert!(replay.step_back());
+        assert_eq!(2, replay.frame());
+        assert_eq!("#####\n#.@O#\n#####\n", replay.warehouse().to_string());
+
+        replay.goto(0);
+        assert!(!replay.step_back());
+        assert_eq!(initial.to_string(), replay.warehouse().to_string());
+    }
+
+    #[test]
+    fn jumps_across_keyframes() {
+        let moves = "><".repeat(KEYFRAME_INTERVAL * 2 + 1);
+        let (initial, mut replay) = replay("######\n#@...#\n######", &moves);
+
+        replay.goto(KEYFRAME_INTERVAL * 3 + 1);
+        assert_eq!("######\n#.@..#\n######\n", replay.warehouse().to_string());
+        replay.goto(KEYFRAME_INTERVAL + 2);
+        assert_eq!(initial.to_string(), replay.warehouse().to_string());
+        replay.goto(usize::MAX);
+        assert_eq!(replay.frame_count() - 1, replay.frame());
+    }
+
+    #[test]
+    fn replays_simultaneous_moves_with_blocking_robots() {
+        let initial = parse_warehouse("#######\n#@.O.@#\n#######").expect("Valid warehouse");
+        let mut warehouse = initial.clone();
+        let mut simulation = Simulation::new(&mut warehouse);
+        simulation.robot_contact = RobotContact::Block;
+        let moves = [vec![Direction::Right; 3], vec![Direction::Left; 3]];
+        simulation.run_robots(&moves, Schedule::Simultaneous);
+        let expected = simulation.warehouse.to_string();
+
+        let mut replay = Replay::new(&initial, &simulation);
+        assert_eq!(4, replay.frame_count());
+        replay.goto(1);
+        assert_eq!(
+            "#######\n#.@O@.#\n#######\n",
+            replay.warehouse().to_string()
+        );
+        replay.goto(3);
+        assert_eq!(expected, replay.warehouse().to_string());
+        assert!(replay
+            .to_string()
+            .contains("Moves 0:> (blocked) 1:< (blocked)"));
+    }
+
+    #[test]
+    fn exports_asciicast() {
+        let (_, replay) = replay("####\n#@.#\n####", ">");
+        let cast = replay.to_asciicast(2.0);
+        let lines: Vec<&str> = cast.lines().collect();
+
+        assert_eq!(3, lines.len());
+        // as wide as the longest status line, "Frame 1/1 Move > GPS: 0"
+        assert_eq!("{\"version\": 2, \"width\": 23, \"height\": 5}", lines[0]);
+        assert_eq!(
+            "[0.500, \"o\", \"\\u001b[H\\u001b[2J####\\r\\n#.@#\\r\\n####\\r\\nFrame 1/1 Move > GPS: 0\\r\\n\"]",
+            lines[2]
+        );
+    }
+}
@@ -1,64 +1,558 @@
+use std::collections::HashSet;
+
 use crate::{
+    history::{FieldChange, History, MoveCommand},
     parser::Direction,
-    warehouse::{Field, Point, Warehouse},
+    warehouse::{iter_points_in_rectangle, Field, Point, Warehouse},
 };
 
 pub(crate) struct Simulation<'a> {
     pub(crate) warehouse: &'a mut Warehouse,
-    pub(crate) robot_position: Point,
+    /// robots are numbered in reading order of their start positions
+    pub(crate) robot_positions: Vec<Point>,
+    pub(crate) robot_contact: RobotContact,
+    /// every move a robot was asked to make, in order
+    pub(crate) log: Vec<LoggedMove>,
+    /// number of times robots were moved, see `LoggedMove::tick`
+    ticks: usize,
+    /// only recorded with `with_history`, to keep long runs cheap
+    history: Option<History>,
+}
+
+/// What happens when a robot runs into another robot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RobotContact {
+    /// the other robot is pushed like a box
+    Push,
+    /// the other robot blocks like a wall
+    Block,
+}
+
+/// How robots with their own move lists take their moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Schedule {
+    /// robots move one after another, each one seeing the moves before it
+    Turns,
+    /// All robots move at once. Moves are planned on the same warehouse, a
+    /// move touching a field another robot's move already touches is blocked,
+    /// so the robot with the lower number wins a conflict. A robot can only
+    /// push robots without a move of their own in the same tick, so every
+    /// robot that changes its position by its own move is logged as moved.
+    Simultaneous,
+}
+
+#[derive(Clone)]
+pub(crate) struct LoggedMove {
+    /// moves made at the same time share their tick, with turns every move has its own
+    pub(crate) tick: usize,
+    pub(crate) robot: usize,
+    pub(crate) direction: Direction,
+    /// false if the robot was blocked by a wall, a robot or a conflict
+    pub(crate) moved: bool,
+    /// boxes pushed along by the robot, not counting other robots it pushes
+    pub(crate) pushed: usize,
 }
 
-impl Simulation<'_> {
+impl<'a> Simulation<'a> {
+    pub(crate) fn new(warehouse: &'a mut Warehouse) -> Self {
+        let robot_positions = warehouse.robot_positions();
+        assert!(
+            !robot_positions.is_empty(),
+            "Warehouse contains a robot field"
+        );
+
+        Simulation::with_robot_positions(warehouse, robot_positions)
+    }
+
+    /// avoids searching the warehouse for the robots
+    pub(crate) fn with_robot_positions(
+        warehouse: &'a mut Warehouse,
+        robot_positions: Vec<Point>,
+    ) -> Self {
+        Simulation {
+            warehouse,
+            robot_positions,
+            robot_contact: RobotContact::Push,
+            log: Vec::new(),
+            ticks: 0,
+            history: None,
+        }
+    }
+
+    /// records every move, so it can be undone and redone
+    pub(crate) fn with_history(mut self) -> Self {
+        self.history = Some(History::default());
+        self
+    }
+
+    /// moves the first robot
     pub(crate) fn move_robot(&mut self, direction: &Direction) {
-        if self.can_move_in_direction(direction, &self.robot_position) {
-            self.move_in_direction(direction, &self.robot_position.clone());
+        self.move_nth_robot(0, direction);
+    }
+
+    pub(crate) fn move_nth_robot(&mut self, robot: usize, direction: &Direction) {
+        let mut moves = vec![None; self.robot_positions.len()];
+        moves[robot] = Some(direction.clone());
+        self.move_robots_simultaneously(&moves);
+    }
+
+    /// Moves every robot with a direction at the same time, see `Schedule::Simultaneous`
+    pub(crate) fn move_robots_simultaneously(&mut self, moves: &[Option<Direction>]) {
+        let robots_before = self.history.is_some().then(|| self.robot_positions.clone());
+        let log_length = self.log.len();
+        let mut touched = HashSet::new();
+        let mut planned = Vec::new();
+        let mut logged = Vec::new();
+        let tick = self.ticks;
+        self.ticks += 1;
+        let moving: Vec<&Point> = moves
+            .iter()
+            .zip(&self.robot_positions)
+            .filter(|(direction, _)| direction.is_some())
+            .map(|(_, position)| position)
+            .collect();
+
+        for (robot, direction) in moves.iter().enumerate() {
+            let Some(direction) = direction else {
+                continue;
+            };
+            let objects = self.pushed_objects(direction, &self.robot_positions[robot]);
+            let objects = objects.filter(|objects| {
+                if objects[1..].iter().any(|object| moving.contains(&object)) {
+                    return false;
+                }
+                let fields = self.touched_fields(objects, direction);
+                if fields.iter().any(|f| touched.contains(f)) {
+                    return false;
+                }
+                touched.extend(fields);
+                true
+            });
+            logged.push(LoggedMove {
+                tick,
+                robot,
+                direction: direction.clone(),
+                moved: objects.is_some(),
+                pushed: objects.as_ref().map_or(0, |objects| {
+                    objects[1..]
+                        .iter()
+                        .filter(|object| self.warehouse[object].is_box_origin())
+                        .count()
+                }),
+            });
+            if let Some(objects) = objects {
+                planned.push((direction, objects));
+            }
         }
+
+        // planned moves touch disjoint fields, so their order does not matter
+        let mut changes = Vec::new();
+        for (direction, objects) in &planned {
+            self.move_in_direction(direction, objects, &mut changes);
+        }
+        if let (Some(history), Some(robots_before)) = (&mut self.history, robots_before) {
+            if !planned.is_empty() {
+                history.record(MoveCommand {
+                    robots_before,
+                    robots_after: self.robot_positions.clone(),
+                    changes,
+                    log_length,
+                    log: Vec::new(),
+                });
+            } else if !logged.is_empty() {
+                // the blocked moves are logged after the undone ones, which can't come back
+                history.discard_undone();
+            }
+        }
+        self.log.extend(logged);
+    }
+
+    /// Runs the moves of every robot, moves[i] belongs to robot i. Robots
+    /// without moves left wait.
+    pub(crate) fn run_robots(&mut self, moves: &[Vec<Direction>], schedule: Schedule) {
+        self.run_robots_with(moves, schedule, |_, _| {});
     }
 
-    fn can_move_in_direction(&self, direction: &Direction, start: &Point) -> bool {
-        let (point, field) = self
-            .warehouse
-            .next_in_direction(start, direction)
-            .expect("Tried to move outside of the warehouse");
-        match field {
-            Field::Empty => true,
-            Field::Wall => false,
-            Field::Box => self.can_move_in_direction(direction, &point),
-            Field::WideBox(wide_box_side) => {
-                if matches!(direction, Direction::Left | Direction::Right) {
-                    self.can_move_in_direction(direction, &point)
-                } else {
-                    let (_, other_side_point) = wide_box_side.other_side(&point);
-                    self.can_move_in_direction(direction, &point)
-                        && self.can_move_in_direction(direction, &other_side_point)
+    /// Like `run_robots`, calling on_tick after every tick with the simulation
+    /// and the moves made in that tick
+    pub(crate) fn run_robots_with(
+        &mut self,
+        moves: &[Vec<Direction>],
+        schedule: Schedule,
+        mut on_tick: impl FnMut(&Simulation, &[LoggedMove]),
+    ) {
+        assert!(
+            moves.len() <= self.robot_positions.len(),
+            "Found {} move sections for {} robots",
+            moves.len(),
+            self.robot_positions.len()
+        );
+        let steps = moves.iter().map(Vec::len).max().unwrap_or(0);
+        for step in 0..steps {
+            match schedule {
+                Schedule::Turns => {
+                    for (robot, robot_moves) in moves.iter().enumerate() {
+                        if let Some(direction) = robot_moves.get(step) {
+                            let log_length = self.log.len();
+                            self.move_nth_robot(robot, direction);
+                            on_tick(self, &self.log[log_length..]);
+                        }
+                    }
                 }
+                Schedule::Simultaneous => {
+                    let tick: Vec<Option<Direction>> =
+                        moves.iter().map(|m| m.get(step).cloned()).collect();
+                    let log_length = self.log.len();
+                    self.move_robots_simultaneously(&tick);
+                    on_tick(self, &self.log[log_length..]);
+                }
+            }
+        }
+    }
+
+    /// Reverts the last move that changed the warehouse and drops it from the
+    /// log, together with the blocked moves after it. Returns false if there
+    /// is nothing to undo or the history is not recorded.
+    pub(crate) fn undo(&mut self) -> bool {
+        let Some(command) = self.history.as_mut().and_then(History::undo) else {
+            return false;
+        };
+        command.undo(self.warehouse);
+        self.robot_positions = command.robots_before.clone();
+        command.log = self.log.split_off(command.log_length);
+        true
+    }
+
+    /// Repeats the last undone move and puts its log entries back. Returns
+    /// false if there is nothing to redo.
+    pub(crate) fn redo(&mut self) -> bool {
+        let Some(command) = self.history.as_mut().and_then(History::redo) else {
+            return false;
+        };
+        command.redo(self.warehouse);
+        self.robot_positions = command.robots_after.clone();
+        self.log.append(&mut command.log);
+        true
+    }
+
+    /// Top left corners of all objects that move when the robot at start
+    /// moves in the direction, including the robot itself and following
+    /// pushes from object to object. None if anything blocks the move.
+    fn pushed_objects(&self, direction: &Direction, start: &Point) -> Option<Vec<Point>> {
+        let mut objects = vec![start.clone()];
+        let mut seen = HashSet::from([start.clone()]);
+        // fields of moving objects whose neighbor in the direction has to make room
+        let mut leading = vec![start.clone()];
+
+        while let Some(point) = leading.pop() {
+            let (next_point, next_field) = self
+                .warehouse
+                .next_in_direction(&point, direction)
+                .expect("Tried to move outside of the warehouse");
+            let top_left = match next_field {
+                Field::Empty => continue,
+                Field::Wall => return None,
+                Field::Box => next_point,
+                Field::WideBox(part) => part.top_left(&next_point),
+                Field::Robot => match self.robot_contact {
+                    RobotContact::Push => next_point,
+                    RobotContact::Block => return None,
+                },
+            };
+            if seen.insert(top_left.clone()) {
+                leading.extend(self.warehouse.box_edge(&top_left, direction));
+                objects.push(top_left);
+            }
+        }
+
+        Some(objects)
+    }
+
+    /// fields of the objects before and after moving them
+    fn object_fields(&self, objects: &[Point]) -> Vec<Point> {
+        objects
+            .iter()
+            .flat_map(|top_left| {
+                let (height, width) = self.warehouse.box_footprint(top_left);
+                iter_points_in_rectangle(
+                    top_left,
+                    &Point {
+                        row: top_left.row + height - 1,
+                        col: top_left.col + width - 1,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn touched_fields(&self, objects: &[Point], direction: &Direction) -> Vec<Point> {
+        self.object_fields(objects)
+            .into_iter()
+            .flat_map(|point| point.iter_direction(direction.clone()).take(2))
+            .collect()
+    }
+
+    /// Moves the objects by one field. All of them are lifted first and then
+    /// put down, so the order of the objects does not matter.
+    fn move_in_direction(
+        &mut self,
+        direction: &Direction,
+        objects: &[Point],
+        changes: &mut Vec<FieldChange>,
+    ) {
+        let lifted: Vec<(Point, Field)> = self
+            .object_fields(objects)
+            .into_iter()
+            .map(|point| {
+                let field = self.warehouse[&point].clone();
+                self.set(&point, Field::Empty, changes);
+                (point, field)
+            })
+            .collect();
+        for (point, field) in lifted {
+            self.set(&next(&point, direction), field, changes);
+        }
+
+        for position in self.robot_positions.iter_mut() {
+            if objects.contains(position) {
+                *position = next(position, direction);
             }
-            Field::Robot => panic!("Tried to move into a robot. Too many robots?"),
         }
     }
 
-    fn move_in_direction(&mut self, direction: &Direction, from: &Point) {
-        let (next_point, next_field) = self
-            .warehouse
-            .next_in_direction(from, direction)
-            .expect("Tried to move outside of the warehouse");
-        match next_field {
-            Field::Box => self.move_in_direction(direction, &next_point),
-            Field::WideBox(wide_box_side) => {
-                if matches!(direction, Direction::Left | Direction::Right) {
-                    self.move_in_direction(direction, &next_point)
-                } else {
-                    let (_, other_side_point) = wide_box_side.other_side(&next_point);
-                    self.move_in_direction(direction, &next_point);
-                    self.move_in_direction(direction, &other_side_point);
+    fn set(&mut self, point: &Point, field: Field, changes: &mut Vec<FieldChange>) {
+        let before = std::mem::replace(&mut self.warehouse[point], field.clone());
+        if self.history.is_some() {
+            changes.push(FieldChange {
+                point: point.clone(),
+                before,
+                after: field,
+            });
+        }
+    }
+}
+
+fn next(point: &Point, direction: &Direction) -> Point {
+    point
+        .iter_direction(direction.clone())
+        .nth(1)
+        .expect("Tried to move outside of the warehouse")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_warehouse;
+
+    use super::*;
+
+    const MAP: &str = "#######\n#.....#\n#.OO..#\n#..O..#\n#..@..#\n#######";
+
+    fn warehouse(map: &str) -> Warehouse {
+        parse_warehouse(map).expect("Valid warehouse")
+    }
+
+    /// every part of a box is where its top left corner says it should be
+    fn assert_consistent(warehouse: &Warehouse, boxes: usize) {
+        let origins: Vec<Point> = warehouse
+            .iter_fields()
+            .filter(|(_, f)| f.is_box_origin())
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(boxes, origins.len());
+        for origin in origins {
+            let (height, width) = warehouse.box_footprint(&origin);
+            for row in 0..height {
+                for col in 0..width {
+                    let point = Point {
+                        row: origin.row + row,
+                        col: origin.col + col,
+                    };
+                    match &warehouse[&point] {
+                        Field::Box => assert_eq!((1, 1), (height, width)),
+                        Field::WideBox(part) => {
+                            assert_eq!(
+                                (row, col, height, width),
+                                (part.row, part.col, part.height, part.width)
+                            )
+                        }
+                        _ => panic!("Box at {:?} is torn apart\n{}", origin, warehouse),
+                    }
                 }
             }
-            Field::Empty => {}
-            Field::Wall => panic!("Tried to move into a wall"),
-            Field::Robot => panic!("Tried to move into a robot. Too many robots?"),
         }
-        self.warehouse[&next_point] = self.warehouse[from].clone();
-        self.warehouse[from] = Field::Empty;
-        self.robot_position = next_point;
+        let robots = warehouse
+            .iter_fields()
+            .filter(|(_, f)| matches!(f, Field::Robot));
+        assert_eq!(1, robots.count());
+    }
+
+    #[test]
+    fn pushes_boxes_of_any_footprint() {
+        let mut warehouse = Warehouse::scaled(&warehouse(MAP), 3, 2);
+        let mut simulation = Simulation::new(&mut warehouse);
+
+        // the lower box pushes the upper right one, the upper left one stays
+        simulation.move_robot(&Direction::Up);
+        assert!(simulation.log[0].moved);
+        assert_eq!(406 + 309 + 509, simulation.warehouse.gps());
+
+        // the upper right box reaches the wall after one more push
+        simulation.move_robot(&Direction::Up);
+        simulation.move_robot(&Direction::Up);
+        assert!(simulation.log[1].moved);
+        assert!(!simulation.log[2].moved);
+        assert_eq!(406 + 209 + 409, simulation.warehouse.gps());
+        assert_consistent(simulation.warehouse, 3);
+    }
+
+    #[test]
+    fn keeps_boxes_intact_under_random_moves() {
+        let map =
+            "##########\n#..O..O..#\n#.OO.O..O#\n#..O@OO..#\n#.O..O.O.#\n#........#\n##########";
+        for (horizontal, vertical) in [(1, 1), (2, 1), (3, 2), (1, 3), (4, 4)] {
+            let mut warehouse = Warehouse::scaled(&warehouse(map), horizontal, vertical);
+            let mut simulation = Simulation::new(&mut warehouse);
+            let mut seed: u64 = 17;
+            for _ in 0..2000 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let direction = match seed >> 62 {
+                    0 => Direction::Left,
+                    1 => Direction::Up,
+                    2 => Direction::Right,
+                    _ => Direction::Down,
+                };
+                simulation.move_robot(&direction);
+                assert_consistent(simulation.warehouse, 12);
+            }
+        }
+    }
+
+    #[test]
+    fn robots_push_or_block_each_other() {
+        let map = "#######\n#@.@O.#\n#######";
+        let point = |col| Point { row: 1, col };
+
+        let mut pushing = warehouse(map);
+        let mut simulation = Simulation::new(&mut pushing);
+        let mut ticks = 0;
+        simulation.run_robots_with(&[vec![Direction::Right; 3]], Schedule::Turns, |_, moves| {
+            assert_eq!(1, moves.len());
+            ticks += 1;
+        });
+        assert_eq!(3, ticks);
+        assert_eq!(vec![point(3), point(4)], simulation.robot_positions);
+        assert_eq!(
+            "#######\n#..@@O#\n#######\n",
+            simulation.warehouse.to_string()
+        );
+        assert!(!simulation.log[2].moved);
+
+        let mut blocking = warehouse(map);
+        let mut simulation = Simulation::new(&mut blocking);
+        simulation.robot_contact = RobotContact::Block;
+        simulation.run_robots(&[vec![Direction::Right; 2]], Schedule::Turns);
+        assert_eq!(vec![point(2), point(3)], simulation.robot_positions);
+    }
+
+    #[test]
+    fn lower_robot_wins_simultaneous_conflicts() {
+        let mut warehouse = warehouse("#####\n#@.@#\n#####");
+        let initial = warehouse.to_string();
+        let mut simulation = Simulation::new(&mut warehouse).with_history();
+        let moves = [vec![Direction::Right], vec![Direction::Left]];
+
+        simulation.run_robots(&moves, Schedule::Simultaneous);
+        assert_eq!("#####\n#.@@#\n#####\n", simulation.warehouse.to_string());
+        assert!(simulation.log[0].moved);
+        assert!(!simulation.log[1].moved);
+
+        // in turns the second robot sees the first one and pushes it back
+        assert!(simulation.undo());
+        assert_eq!(initial, simulation.warehouse.to_string());
+        simulation.run_robots(&moves, Schedule::Turns);
+        assert_eq!("#####\n#@@.#\n#####\n", simulation.warehouse.to_string());
+    }
+
+    #[test]
+    fn robots_do_not_push_moving_robots_simultaneously() {
+        let mut warehouse = warehouse("#######\n#@@O..#\n#######");
+        let mut simulation = Simulation::new(&mut warehouse);
+
+        // the second robot moves on its own, the first one may not push it
+        let moves = [vec![Direction::Right], vec![Direction::Right]];
+        simulation.run_robots(&moves, Schedule::Simultaneous);
+        assert_eq!(
+            "#######\n#@.@O.#\n#######\n",
+            simulation.warehouse.to_string()
+        );
+        assert!(!simulation.log[0].moved);
+        assert!(simulation.log[1].moved);
+
+        // a waiting robot is pushed along
+        simulation.run_robots(&[vec![Direction::Right; 2]], Schedule::Simultaneous);
+        assert_eq!(
+            "#######\n#..@@O#\n#######\n",
+            simulation.warehouse.to_string()
+        );
+    }
+
+    #[test]
+    fn undoes_and_redoes_wide_box_pushes() {
+        let mut warehouse = Warehouse::scaled_up(&warehouse(MAP));
+        let initial = warehouse.to_string();
+        let mut simulation = Simulation::new(&mut warehouse).with_history();
+
+        // pushes both boxes, is blocked by the wall, steps aside
+        simulation.move_robot(&Direction::Up);
+        simulation.move_robot(&Direction::Up);
+        simulation.move_robot(&Direction::Left);
+        let pushed = simulation.warehouse.to_string();
+        assert_eq!(3, simulation.log.len());
+
+        assert!(simulation.undo());
+        assert_eq!(2, simulation.log.len());
+        assert!(simulation.undo());
+        assert!(!simulation.undo());
+        assert_eq!(initial, simulation.warehouse.to_string());
+        assert!(simulation.log.is_empty());
+
+        assert!(simulation.redo());
+        assert!(simulation.redo());
+        assert!(!simulation.redo());
+        assert_eq!(pushed, simulation.warehouse.to_string());
+        // the move into the wall is back in the log
+        assert_eq!(3, simulation.log.len());
+        assert!(!simulation.log[1].moved);
+
+        simulation.undo();
+        simulation.move_robot(&Direction::Left);
+        assert!(!simulation.redo());
+
+        let mut quiet = parse_warehouse(MAP).expect("Valid warehouse");
+        let mut simulation = Simulation::new(&mut quiet);
+        simulation.move_robot(&Direction::Up);
+        assert!(!simulation.undo());
+    }
+
+    #[test]
+    fn blocked_moves_end_redoing() {
+        let mut warehouse = warehouse(MAP);
+        let mut simulation = Simulation::new(&mut warehouse).with_history();
+
+        simulation.move_robot(&Direction::Up);
+        assert!(simulation.undo());
+        // into the wall, which keeps the log in order
+        simulation.move_robot(&Direction::Down);
+        assert!(!simulation.redo());
+        assert!(!simulation.undo());
+        assert_eq!(1, simulation.log.len());
+        assert!(!simulation.log[0].moved);
+
+        // undoing a later move keeps the blocked one
+        simulation.move_robot(&Direction::Left);
+        assert!(simulation.undo());
+        assert_eq!(1, simulation.log.len());
     }
 }
@@ -0,0 +1,462 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fmt::Display,
+    rc::Rc,
+};
+
+use crate::{
+    parser::Direction,
+    simulation::Simulation,
+    warehouse::{Field, Point, Warehouse},
+};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+];
+
+pub(crate) enum Goal {
+    /// boxes at exactly these positions, the left side for wide boxes
+    Layout(Vec<Point>),
+    MinGps,
+    MaxGps,
+}
+
+impl Goal {
+    /// boxes placed like in the target warehouse, the robot may end up anywhere
+    pub(crate) fn layout(target: &Warehouse) -> Self {
+        Goal::Layout(box_positions(target))
+    }
+}
+
+pub(crate) struct Solution {
+    pub(crate) moves: Vec<Direction>,
+    pub(crate) gps: usize,
+}
+
+impl Solution {
+    /// moves in the format of the input file
+    pub(crate) fn move_string(&self) -> String {
+        self.moves.iter().map(|d| d.to_string()).collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum SolveError {
+    /// the target layout has a different number of boxes than the warehouse
+    BoxCount { expected: usize, found: usize },
+    /// a target box lies outside the warehouse or covers a wall
+    BlockedTarget(Point),
+    /// every reachable state was visited without reaching the layout
+    Unreachable,
+    /// the layout was not reached within the given number of states
+    StateLimit(usize),
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::BoxCount { expected, found } => {
+                write!(f, "Target has {} boxes, expected {}", found, expected)
+            }
+            SolveError::BlockedTarget(point) => write!(
+                f,
+                "Target box at row {}, column {} covers a wall",
+                point.row, point.col
+            ),
+            SolveError::Unreachable => write!(f, "The target layout is unreachable"),
+            SolveError::StateLimit(max_states) => {
+                write!(f, "No solution within {} states", max_states)
+            }
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+/// Positions of the robots in the order of their numbers, followed by the top
+/// left corners of the boxes in reading order
+type State = Rc<[Point]>;
+
+struct Node {
+    state: State,
+    parent: Option<(usize, Direction)>,
+    moves: usize,
+}
+
+/// Walls of the warehouse and the fields of a box, to put a state back
+/// together. Every box of a warehouse has the same footprint.
+struct Floor {
+    walls: Warehouse,
+    /// fields of a box relative to its top left corner
+    box_fields: Vec<(Point, Field)>,
+    robots: usize,
+}
+
+impl Floor {
+    fn new(warehouse: &Warehouse) -> Self {
+        let mut walls = warehouse.clone();
+        let mut box_fields = Vec::new();
+        for (point, field) in warehouse.iter_fields() {
+            if box_fields.is_empty() && field.is_box_origin() {
+                let (height, width) = warehouse.box_footprint(&point);
+                for row in 0..height {
+                    for col in 0..width {
+                        let part = Point {
+                            row: point.row + row,
+                            col: point.col + col,
+                        };
+                        box_fields.push((Point { row, col }, warehouse[&part].clone()));
+                    }
+                }
+            }
+            if !matches!(field, Field::Wall) {
+                walls[&point] = Field::Empty;
+            }
+        }
+
+        Floor {
+            walls,
+            box_fields,
+            robots: warehouse.robot_positions().len(),
+        }
+    }
+
+    fn state(&self, robots: &[Point], warehouse: &Warehouse) -> State {
+        robots
+            .iter()
+            .cloned()
+            .chain(box_positions(warehouse))
+            .collect()
+    }
+
+    fn robots<'a>(&self, state: &'a State) -> &'a [Point] {
+        &state[..self.robots]
+    }
+
+    fn boxes<'a>(&self, state: &'a State) -> &'a [Point] {
+        &state[self.robots..]
+    }
+
+    fn warehouse(&self, state: &State) -> Warehouse {
+        let mut warehouse = self.walls.clone();
+        for robot in self.robots(state) {
+            warehouse[robot] = Field::Robot;
+        }
+        for top_left in self.boxes(state) {
+            for (offset, field) in &self.box_fields {
+                let point = Point {
+                    row: top_left.row + offset.row,
+                    col: top_left.col + offset.col,
+                };
+                warehouse[&point] = field.clone();
+            }
+        }
+        warehouse
+    }
+
+    /// every target needs room for a whole box on the floor
+    fn check_targets(&self, targets: &[Point], start: &State) -> Result<(), SolveError> {
+        let expected = self.boxes(start).len();
+        if targets.len() != expected {
+            return Err(SolveError::BoxCount {
+                expected,
+                found: targets.len(),
+            });
+        }
+        for target in targets {
+            let blocked = self.box_fields.iter().any(|(offset, _)| {
+                let point = Point {
+                    row: target.row + offset.row,
+                    col: target.col + offset.col,
+                };
+                !self.walls.in_range(&point) || matches!(self.walls[&point], Field::Wall)
+            });
+            if blocked {
+                return Err(SolveError::BlockedTarget(target.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Searches for the shortest move sequence reaching the goal, with A* for a
+/// layout and by visiting every reachable state for min or max GPS.
+///
+/// Only the first robot is moved, other robots are pushed around like boxes.
+/// At most max_states distinct states are visited. A layout that is not
+/// reached until then is an error, for min or max GPS the best state found
+/// so far is returned.
+pub(crate) fn solve(
+    warehouse: &Warehouse,
+    goal: &Goal,
+    max_states: usize,
+) -> Result<Solution, SolveError> {
+    let robots = warehouse.robot_positions();
+    assert!(!robots.is_empty(), "Warehouse contains a robot field");
+    let floor = Floor::new(warehouse);
+    let start = floor.state(&robots, warehouse);
+    if let Goal::Layout(targets) = goal {
+        floor.check_targets(targets, &start)?;
+    }
+    let mut queue = BinaryHeap::from([Reverse((heuristic(floor.boxes(&start), goal), 0, 0))]);
+    let mut index_of = HashMap::from([(start.clone(), 0)]);
+    let mut nodes = vec![Node {
+        state: start,
+        parent: None,
+        moves: 0,
+    }];
+    let mut best = (0, gps(floor.boxes(&nodes[0].state)));
+    let mut limited = false;
+
+    while let Some(Reverse((_, moves, index))) = queue.pop() {
+        // the state was queued again with fewer moves
+        if moves > nodes[index].moves {
+            continue;
+        }
+        let state = nodes[index].state.clone();
+        let boxes = floor.boxes(&state);
+        match goal {
+            Goal::Layout(targets) if boxes == targets.as_slice() => {
+                return Ok(solution(&nodes, index, gps(boxes)));
+            }
+            Goal::MinGps if gps(boxes) < best.1 => best = (index, gps(boxes)),
+            Goal::MaxGps if gps(boxes) > best.1 => best = (index, gps(boxes)),
+            _ => {}
+        }
+
+        let current = floor.warehouse(&state);
+        for direction in DIRECTIONS {
+            let mut warehouse = current.clone();
+            let mut simulation =
+                Simulation::with_robot_positions(&mut warehouse, floor.robots(&state).to_vec());
+            simulation.move_robot(&direction);
+            if !simulation.log[0].moved {
+                continue;
+            }
+            let robots = simulation.robot_positions;
+            if let Goal::Layout(targets) = goal {
+                if is_deadlocked(&warehouse, targets) {
+                    continue;
+                }
+            }
+
+            let next = floor.state(&robots, &warehouse);
+            let next_index = match index_of.get(&next) {
+                Some(&known) if nodes[known].moves <= moves + 1 => continue,
+                Some(&known) => known,
+                None if index_of.len() >= max_states => {
+                    limited = true;
+                    continue;
+                }
+                None => {
+                    index_of.insert(next.clone(), nodes.len());
+                    nodes.push(Node {
+                        state: next,
+                        parent: None,
+                        moves: 0,
+                    });
+                    nodes.len() - 1
+                }
+            };
+            let node = &mut nodes[next_index];
+            node.parent = Some((index, direction));
+            node.moves = moves + 1;
+            let estimate = node.moves + heuristic(floor.boxes(&node.state), goal);
+            queue.push(Reverse((estimate, node.moves, next_index)));
+        }
+    }
+
+    match goal {
+        Goal::Layout(_) if limited => Err(SolveError::StateLimit(max_states)),
+        Goal::Layout(_) => Err(SolveError::Unreachable),
+        Goal::MinGps | Goal::MaxGps => Ok(solution(&nodes, best.0, best.1)),
+    }
+}
+
+fn solution(nodes: &[Node], mut index: usize, gps: usize) -> Solution {
+    let mut moves = Vec::new();
+    while let Some((parent, direction)) = &nodes[index].parent {
+        moves.push(direction.clone());
+        index = *parent;
+    }
+    moves.reverse();
+
+    Solution { moves, gps }
+}
+
+fn box_positions(warehouse: &Warehouse) -> Vec<Point> {
+    warehouse
+        .iter_fields()
+        .filter(|(_, f)| f.is_box_origin())
+        .map(|(p, _)| p)
+        .collect()
+}
+
+fn gps(boxes: &[Point]) -> usize {
+    boxes.iter().map(|b| 100 * b.row + b.col).sum()
+}
+
+/// A move pushes every box by at most one field, even a whole row of them, so
+/// the largest distance of a box to its nearest target is a lower bound for
+/// the remaining moves. It changes by at most one per move, so the first time
+/// A* takes a state from the queue it has been reached with the fewest moves.
+fn heuristic(boxes: &[Point], goal: &Goal) -> usize {
+    let Goal::Layout(targets) = goal else {
+        return 0;
+    };
+    boxes
+        .iter()
+        .map(|b| {
+            targets
+                .iter()
+                .map(|t| b.row.abs_diff(t.row) + b.col.abs_diff(t.col))
+                .min()
+                .unwrap_or(0)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// A box can never be pushed in a direction with a wall in front of any of
+/// its fields, or if walls take all places behind it the robot could push
+/// from. A box that cannot move along either axis and is not on a target
+/// makes the layout unreachable.
+fn is_deadlocked(warehouse: &Warehouse, targets: &[Point]) -> bool {
+    let walls = |top_left: &Point, direction: &Direction| {
+        warehouse
+            .box_edge(top_left, direction)
+            .iter()
+            .map(|p| {
+                matches!(
+                    warehouse.next_in_direction(p, direction),
+                    Some((_, Field::Wall)) | None
+                )
+            })
+            .collect::<Vec<bool>>()
+    };
+    let frozen = |top_left: &Point, forward: Direction, backward: Direction| {
+        let (ahead, behind) = (walls(top_left, &forward), walls(top_left, &backward));
+        let any = |walls: &[bool]| walls.iter().any(|w| *w);
+        let all = |walls: &[bool]| walls.iter().all(|w| *w);
+        (any(&ahead) || all(&behind)) && (any(&behind) || all(&ahead))
+    };
+
+    box_positions(warehouse)
+        .into_iter()
+        .filter(|b| !targets.contains(b))
+        .any(|b| {
+            frozen(&b, Direction::Up, Direction::Down)
+                && frozen(&b, Direction::Left, Direction::Right)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_warehouse;
+
+    use super::*;
+
+    fn warehouse(map: &str) -> Warehouse {
+        parse_warehouse(map).expect("Valid warehouse")
+    }
+
+    /// applies the moves of the solution and returns the resulting warehouse
+    fn apply(warehouse: &Warehouse, solution: &Solution) -> Warehouse {
+        let mut result = warehouse.clone();
+        let mut simulation = Simulation::new(&mut result);
+        for direction in &solution.moves {
+            simulation.move_robot(direction);
+        }
+        result
+    }
+
+    #[test]
+    fn pushes_box_onto_target() {
+        let start = warehouse("######\n#@...#\n#.O..#\n#....#\n######");
+        let target = warehouse("######\n#....#\n#....#\n#..O.#\n######");
+
+        let solution = solve(&start, &Goal::layout(&target), 10_000).expect("Solvable");
+
+        assert_eq!(5, solution.moves.len());
+        assert_eq!(303, solution.gps);
+        assert_eq!(303, apply(&start, &solution).gps());
+    }
+
+    #[test]
+    fn pushes_rows_of_boxes_and_other_robots() {
+        // a single move pushes both boxes, so the distances must not be added up
+        let start = warehouse("#######\n#@OO..#\n#######");
+        let target = warehouse("#######\n#...OO#\n#######");
+        let solution = solve(&start, &Goal::layout(&target), 10_000).expect("Solvable");
+        assert_eq!(">>", solution.move_string());
+
+        // the second robot is part of the state and pushed along with the box
+        let start = warehouse("#######\n#@.@O.#\n#.....#\n#######");
+        let target = warehouse("#######\n#....O#\n#.....#\n#######");
+        let solution = solve(&start, &Goal::layout(&target), 10_000).expect("Solvable");
+        assert_eq!(">>", solution.move_string());
+        assert_eq!(
+            box_positions(&target),
+            box_positions(&apply(&start, &solution))
+        );
+    }
+
+    #[test]
+    fn detects_unreachable_layout() {
+        // the box in the corner can never be pushed again
+        let start = warehouse("#####\n#O..#\n#.@.#\n#...#\n#####");
+        let target = warehouse("#####\n#...#\n#.O.#\n#...#\n#####");
+
+        assert_eq!(
+            Some(SolveError::Unreachable),
+            solve(&start, &Goal::layout(&target), 10_000).err()
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_targets() {
+        let start = warehouse("######\n#@O..#\n#....#\n######");
+        let error = |target: Goal| solve(&start, &target, 10_000).err();
+
+        assert_eq!(
+            Some(SolveError::BoxCount {
+                expected: 1,
+                found: 2
+            }),
+            error(Goal::layout(&warehouse("######\n#..OO#\n#....#\n######")))
+        );
+        assert_eq!(
+            Some(SolveError::BlockedTarget(Point { row: 0, col: 2 })),
+            error(Goal::Layout(vec![Point { row: 0, col: 2 }]))
+        );
+        assert_eq!(
+            Some(SolveError::BlockedTarget(Point { row: 9, col: 9 })),
+            error(Goal::Layout(vec![Point { row: 9, col: 9 }]))
+        );
+
+        // the right half of a wide box would cover the wall
+        let wide = Warehouse::scaled_up(&start);
+        assert_eq!(
+            Some(SolveError::BlockedTarget(Point { row: 1, col: 9 })),
+            solve(&wide, &Goal::Layout(vec![Point { row: 1, col: 9 }]), 10_000).err()
+        );
+    }
+
+    #[test]
+    fn minimizes_and_maximizes_gps_with_wide_boxes() {
+        let start = Warehouse::scaled_up(&warehouse("#####\n#...#\n#.O.#\n#..@#\n#####"));
+
+        let min = solve(&start, &Goal::MinGps, 100_000).expect("Always has a solution");
+        let max = solve(&start, &Goal::MaxGps, 100_000).expect("Always has a solution");
+
+        assert_eq!(102, min.gps);
+        assert_eq!(min.gps, apply(&start, &min).gps());
+        assert_eq!(306, max.gps);
+        assert_eq!(max.gps, apply(&start, &max).gps());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    parser::Direction,
+    simulation::{Schedule, Simulation},
+    warehouse::Warehouse,
+};
+
+/// Outcome of a simulation, for running many move lists without printing
+#[derive(Debug)]
+pub(crate) struct Summary {
+    pub(crate) gps: usize,
+    pub(crate) moved: usize,
+    pub(crate) blocked: usize,
+    /// sum of the boxes pushed by every move
+    pub(crate) boxes_displaced: usize,
+    /// most boxes pushed by a single move
+    pub(crate) max_push_chain: usize,
+    pub(crate) elapsed: Duration,
+}
+
+impl Summary {
+    pub(crate) fn new(simulation: &Simulation, elapsed: Duration) -> Self {
+        let log = &simulation.log;
+
+        Summary {
+            gps: simulation.warehouse.gps(),
+            moved: log.iter().filter(|m| m.moved).count(),
+            blocked: log.iter().filter(|m| !m.moved).count(),
+            boxes_displaced: log.iter().map(|m| m.pushed).sum(),
+            max_push_chain: log.iter().map(|m| m.pushed).max().unwrap_or(0),
+            elapsed,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GPS: {}, moved: {}, blocked: {}, boxes displaced: {}, longest push: {}, time: {:?}",
+            self.gps,
+            self.moved,
+            self.blocked,
+            self.boxes_displaced,
+            self.max_push_chain,
+            self.elapsed
+        )
+    }
+}
+
+/// runs the moves of every robot in turns without printing anything
+pub(crate) fn run_quiet(warehouse: &mut Warehouse, moves: &[Vec<Direction>]) -> Summary {
+    let start = Instant::now();
+    let mut simulation = Simulation::new(warehouse);
+    simulation.run_robots(moves, Schedule::Turns);
+
+    Summary::new(&simulation, start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_str;
+
+    use super::*;
+
+    #[test]
+    fn counts_moves_and_pushes() {
+        let input = parse_str("#########\n#.@O.O..#\n#.......#\n#########\n\n>>>><v<").unwrap();
+        let mut warehouse = input.warehouse;
+
+        let summary = run_quiet(&mut warehouse, &input.moves);
+
+        assert_eq!(106 + 107, summary.gps);
+        assert_eq!((6, 1), (summary.moved, summary.blocked));
+        assert_eq!(5, summary.boxes_displaced);
+        assert_eq!(2, summary.max_push_chain);
+
+        // the robots push each other, only the last move also pushes the box
+        let input = parse_str("########\n#@.@O..#\n########\n\n>>>\n\n<").unwrap();
+        let mut warehouse = input.warehouse;
+
+        let summary = run_quiet(&mut warehouse, &input.moves);
+
+        assert_eq!((4, 0), (summary.moved, summary.blocked));
+        assert_eq!(1, summary.boxes_displaced);
+        assert_eq!(1, summary.max_push_chain);
+    }
+}
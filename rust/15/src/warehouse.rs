@@ -6,6 +6,7 @@ use std::{
 
 use crate::parser::Direction;
 
+#[derive(Clone)]
 pub(crate) struct Warehouse {
     inner: Vec<Vec<Field>>,
     width: usize,
@@ -24,27 +25,48 @@ impl Warehouse {
         }
     }
 
+    /// doubles the width, boxes become two fields wide
     pub(crate) fn scaled_up(original: &Warehouse) -> Self {
-        let height = original.height;
-        let width = original.width * 2;
-        let rows = original
-            .inner
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .flat_map(|f| match f {
-                        Field::Empty => [Field::Empty, Field::Empty],
-                        Field::Wall => [Field::Wall, Field::Wall],
-                        Field::Box => [
-                            Field::WideBox(WideBoxSide::Left),
-                            Field::WideBox(WideBoxSide::Right),
-                        ],
-                        Field::WideBox(_) => panic!("Cannot scale up a wide box"),
-                        Field::Robot => [Field::Robot, Field::Empty],
-                    })
-                    .collect()
-            })
-            .collect();
+        Warehouse::scaled(original, 2, 1)
+    }
+
+    /// Scales every field to horizontal x vertical fields. A box becomes a
+    /// single box with that footprint, a robot stays in the top left corner.
+    pub(crate) fn scaled(original: &Warehouse, horizontal: usize, vertical: usize) -> Self {
+        assert!(
+            horizontal > 0 && vertical > 0,
+            "Scale factors must be positive"
+        );
+        let height = original.height * vertical;
+        let width = original.width * horizontal;
+        let rows = iter_points_in_rectangle(
+            &Point { row: 0, col: 0 },
+            &Point {
+                row: height - 1,
+                col: width - 1,
+            },
+        )
+        .map(|p| {
+            let (row, col) = (p.row % vertical, p.col % horizontal);
+            match &original.inner[p.row / vertical][p.col / horizontal] {
+                Field::Empty => Field::Empty,
+                Field::Wall => Field::Wall,
+                Field::Box if horizontal == 1 && vertical == 1 => Field::Box,
+                Field::Box => Field::WideBox(BoxPart {
+                    row,
+                    col,
+                    height: vertical,
+                    width: horizontal,
+                }),
+                Field::WideBox(_) => panic!("Cannot scale up a wide box"),
+                Field::Robot if row == 0 && col == 0 => Field::Robot,
+                Field::Robot => Field::Empty,
+            }
+        })
+        .collect::<Vec<Field>>()
+        .chunks(width)
+        .map(|row| row.to_vec())
+        .collect();
 
         Warehouse {
             height,
@@ -53,6 +75,47 @@ impl Warehouse {
         }
     }
 
+    /// all robots in reading order
+    pub(crate) fn robot_positions(&self) -> Vec<Point> {
+        self.iter_fields()
+            .filter(|(_, f)| matches!(f, Field::Robot))
+            .map(|(p, _)| p)
+            .collect()
+    }
+
+    /// fields of the box that touch the fields next to it in the direction
+    pub(crate) fn box_edge(&self, top_left: &Point, direction: &Direction) -> Vec<Point> {
+        let (height, width) = self.box_footprint(top_left);
+        let (rows, cols) = match direction {
+            Direction::Left => (
+                top_left.row..top_left.row + height,
+                top_left.col..top_left.col + 1,
+            ),
+            Direction::Right => (
+                top_left.row..top_left.row + height,
+                top_left.col + width - 1..top_left.col + width,
+            ),
+            Direction::Up => (
+                top_left.row..top_left.row + 1,
+                top_left.col..top_left.col + width,
+            ),
+            Direction::Down => (
+                top_left.row + height - 1..top_left.row + height,
+                top_left.col..top_left.col + width,
+            ),
+        };
+        rows.flat_map(|row| cols.clone().map(move |col| Point { row, col }))
+            .collect()
+    }
+
+    /// height and width of the box with the top left corner
+    pub(crate) fn box_footprint(&self, top_left: &Point) -> (usize, usize) {
+        match &self[top_left] {
+            Field::WideBox(part) => (part.height, part.width),
+            _ => (1, 1),
+        }
+    }
+
     pub(crate) fn in_range(&self, point: &Point) -> bool {
         (0..self.width).contains(&point.col) && (0..self.height).contains(&point.row)
     }
@@ -67,7 +130,7 @@ impl Warehouse {
 
     pub(crate) fn gps(&self) -> usize {
         self.iter_fields()
-            .filter(|(_, f)| matches!(f, Field::Box | Field::WideBox(WideBoxSide::Left)))
+            .filter(|(_, f)| f.is_box_origin())
             .map(|(p, _)| 100 * p.row + p.col)
             .sum()
     }
@@ -101,7 +164,7 @@ impl Warehouse {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct Point {
     pub(crate) row: usize,
     pub(crate) col: usize,
@@ -196,45 +259,51 @@ pub(crate) enum Field {
     Empty,
     Wall,
     Box,
-    WideBox(WideBoxSide),
+    WideBox(BoxPart),
     Robot,
 }
 
+/// Field covered by a box larger than a single field
 #[derive(Clone)]
-pub(crate) enum WideBoxSide {
-    Left,
-    Right,
+pub(crate) struct BoxPart {
+    /// position of the field inside of the box
+    pub(crate) row: usize,
+    pub(crate) col: usize,
+    pub(crate) height: usize,
+    pub(crate) width: usize,
 }
 
-impl WideBoxSide {
-    pub fn other_side(&self, position: &Point) -> (Self, Point) {
-        match self {
-            WideBoxSide::Left => (
-                WideBoxSide::Right,
-                Point {
-                    row: position.row,
-                    col: position.col + 1,
-                },
-            ),
-            WideBoxSide::Right => (
-                WideBoxSide::Left,
-                Point {
-                    row: position.row,
-                    col: position.col - 1,
-                },
-            ),
+impl BoxPart {
+    pub(crate) fn is_top_left(&self) -> bool {
+        self.row == 0 && self.col == 0
+    }
+
+    /// top left corner of the box, if this part is at position
+    pub(crate) fn top_left(&self, position: &Point) -> Point {
+        Point {
+            row: position.row - self.row,
+            col: position.col - self.col,
         }
     }
 }
 
 impl Field {
-    pub(crate) fn from(c: char) -> Self {
+    /// true for single field boxes and the top left corner of larger boxes
+    pub(crate) fn is_box_origin(&self) -> bool {
+        match self {
+            Field::Box => true,
+            Field::WideBox(part) => part.is_top_left(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn from_char(c: char) -> Option<Self> {
         match c {
-            '.' => Field::Empty,
-            '#' => Field::Wall,
-            'O' => Field::Box,
-            '@' => Field::Robot,
-            _ => panic!("Invalid char"),
+            '.' => Some(Field::Empty),
+            '#' => Some(Field::Wall),
+            'O' => Some(Field::Box),
+            '@' => Some(Field::Robot),
+            _ => None,
         }
     }
 }
@@ -246,8 +315,10 @@ impl Display for Field {
             Field::Wall => write!(f, "#"),
             Field::Box => write!(f, "O"),
             Field::Robot => write!(f, "@"),
-            Field::WideBox(WideBoxSide::Left) => write!(f, "["),
-            Field::WideBox(WideBoxSide::Right) => write!(f, "]"),
+            Field::WideBox(part) if part.width == 1 => write!(f, "O"),
+            Field::WideBox(part) if part.col == 0 => write!(f, "["),
+            Field::WideBox(part) if part.col + 1 == part.width => write!(f, "]"),
+            Field::WideBox(_) => write!(f, "="),
         }
     }
 }
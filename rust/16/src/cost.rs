@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+use crate::utils::Direction;
+
+/// Rules for the cost of moving through the maze
+pub trait CostModel {
+    /// cost of moving forward by one field
+    fn step_cost(&self) -> usize;
+
+    /// cost of a single 90° turn
+    fn turn_cost(&self) -> usize;
+
+    fn u_turn(&self) -> UTurn;
+
+    /// direction faced before the first step
+    fn start_heading(&self) -> Direction;
+
+    /// Cost of turning from one heading to the next and stepping forward,
+    /// None if the model does not allow that turn.
+    fn move_cost(&self, heading: &Direction, next: &Direction) -> Option<usize> {
+        let turning = match heading.quarter_turns_to(next) {
+            2 => match self.u_turn() {
+                UTurn::TwoTurns => 2 * self.turn_cost(),
+                UTurn::Fixed(cost) => cost,
+                UTurn::Forbidden => return None,
+            },
+            turns => turns * self.turn_cost(),
+        };
+        Some(turning + self.step_cost())
+    }
+}
+
+/// How turning around is priced
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum UTurn {
+    /// two 90° turns
+    TwoTurns,
+    Fixed(usize),
+    Forbidden,
+}
+
+impl FromStr for UTurn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "two-turns" => Ok(UTurn::TwoTurns),
+            "forbidden" => Ok(UTurn::Forbidden),
+            cost => match cost.parse() {
+                Ok(cost) => Ok(UTurn::Fixed(cost)),
+                Err(_) => bail!(
+                    "Unknown U-turn policy {}, expected two-turns, forbidden or a cost",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+/// Cost model with fixed costs, the default are the rules of the puzzle
+#[derive(Debug, Clone)]
+pub struct Costs {
+    pub step: usize,
+    pub turn: usize,
+    pub u_turn: UTurn,
+    pub start_heading: Direction,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            step: 1,
+            turn: 1000,
+            u_turn: UTurn::TwoTurns,
+            start_heading: Direction::Right,
+        }
+    }
+}
+
+impl CostModel for Costs {
+    fn step_cost(&self) -> usize {
+        self.step
+    }
+
+    fn turn_cost(&self) -> usize {
+        self.turn
+    }
+
+    fn u_turn(&self) -> UTurn {
+        self.u_turn
+    }
+
+    fn start_heading(&self) -> Direction {
+        self.start_heading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_turns_and_u_turns() {
+        let mut costs = Costs::default();
+        assert_eq!(Some(1), costs.move_cost(&Direction::Up, &Direction::Up));
+        assert_eq!(
+            Some(1001),
+            costs.move_cost(&Direction::Up, &Direction::Left)
+        );
+        assert_eq!(
+            Some(2001),
+            costs.move_cost(&Direction::Up, &Direction::Down)
+        );
+
+        costs.u_turn = "forbidden".parse().unwrap();
+        assert_eq!(None, costs.move_cost(&Direction::Up, &Direction::Down));
+
+        costs.u_turn = "5".parse().unwrap();
+        costs.step = 10;
+        assert_eq!(Some(15), costs.move_cost(&Direction::Up, &Direction::Down));
+    }
+}
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+use crate::utils::{Direction, Point};
+
+pub enum Algorithm {
+    /// long winding corridors with few junctions
+    Backtracker,
+    /// many short dead ends branching off everywhere
+    Prim,
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "backtracker" => Ok(Algorithm::Backtracker),
+            "prim" => Ok(Algorithm::Prim),
+            _ => bail!("Unknown algorithm {}, expected backtracker or prim", s),
+        }
+    }
+}
+
+pub struct Options {
+    pub algorithm: Algorithm,
+    pub width: usize,
+    pub height: usize,
+    /// Share of dead ends opened up into loops, from 0 for a perfect maze with
+    /// exactly one path between two points to 1 for a maze without dead ends
+    pub loopiness: f64,
+    pub seed: u64,
+}
+
+/// Generates a maze in the puzzle format, the start in the bottom left and
+/// the end in the top right corner. Corridors run between the fields with odd
+/// coordinates, so even sizes are rounded up to keep the maze enclosed by walls.
+/// Mazes are at least 5 fields wide, to keep the start and end apart.
+pub fn generate(options: &Options) -> String {
+    let width = options.width.max(5) / 2 * 2 + 1;
+    let height = options.height.max(3) / 2 * 2 + 1;
+    let mut grid = Grid {
+        open: vec![vec![false; width]; height],
+        rng: Rng::new(options.seed),
+    };
+
+    match options.algorithm {
+        Algorithm::Backtracker => grid.backtracker(),
+        Algorithm::Prim => grid.prim(),
+    }
+    grid.braid(options.loopiness);
+
+    let start = Point {
+        row: height - 2,
+        col: 1,
+    };
+    let end = Point {
+        row: 1,
+        col: width - 2,
+    };
+    let mut maze = String::new();
+    for (row, fields) in grid.open.iter().enumerate() {
+        for (col, open) in fields.iter().enumerate() {
+            let point = Point { row, col };
+            maze.push(match open {
+                _ if point == start => 'S',
+                _ if point == end => 'E',
+                true => '.',
+                false => '#',
+            });
+        }
+        maze.push('\n');
+    }
+    maze
+}
+
+struct Grid {
+    open: Vec<Vec<bool>>,
+    rng: Rng,
+}
+
+impl Grid {
+    fn is_cell(&self, point: &Point) -> bool {
+        point.row % 2 == 1
+            && point.col % 2 == 1
+            && point.row < self.open.len() - 1
+            && point.col < self.open[0].len() - 1
+    }
+
+    /// cells two fields away and the wall between them
+    fn neighbour_cells(&self, cell: &Point) -> Vec<(Point, Point)> {
+        Direction::ALL
+            .iter()
+            .filter_map(|direction| {
+                let wall = cell.next_in_direction(direction)?;
+                let next = wall.next_in_direction(direction)?;
+                self.is_cell(&next).then_some((next, wall))
+            })
+            .collect()
+    }
+
+    fn carve(&mut self, point: &Point) {
+        self.open[point.row][point.col] = true;
+    }
+
+    fn is_open(&self, point: &Point) -> bool {
+        self.open[point.row][point.col]
+    }
+
+    /// Randomized depth first search, walking on until it gets stuck
+    fn backtracker(&mut self) {
+        let first = Point { row: 1, col: 1 };
+        self.carve(&first);
+        let mut stack = vec![first];
+        while let Some(cell) = stack.last() {
+            let unvisited: Vec<(Point, Point)> = self
+                .neighbour_cells(cell)
+                .into_iter()
+                .filter(|(next, _)| !self.is_open(next))
+                .collect();
+            if unvisited.is_empty() {
+                stack.pop();
+                continue;
+            }
+            let (next, wall) = unvisited[self.rng.below(unvisited.len())].clone();
+            self.carve(&wall);
+            self.carve(&next);
+            stack.push(next);
+        }
+    }
+
+    /// Randomized Prim's algorithm, growing the maze from a random wall of its border
+    fn prim(&mut self) {
+        let first = Point { row: 1, col: 1 };
+        self.carve(&first);
+        let mut frontier = self.neighbour_cells(&first);
+        while !frontier.is_empty() {
+            let (next, wall) = frontier.swap_remove(self.rng.below(frontier.len()));
+            if self.is_open(&next) {
+                continue;
+            }
+            self.carve(&wall);
+            self.carve(&next);
+            frontier.extend(self.neighbour_cells(&next));
+        }
+    }
+
+    /// removes a random wall of each dead end with the given probability
+    fn braid(&mut self, loopiness: f64) {
+        for row in (1..self.open.len() - 1).step_by(2) {
+            for col in (1..self.open[0].len() - 1).step_by(2) {
+                let cell = Point { row, col };
+                let (open, closed): (Vec<_>, Vec<_>) = self
+                    .neighbour_cells(&cell)
+                    .into_iter()
+                    .partition(|(_, wall)| self.is_open(wall));
+                if open.len() == 1 && !closed.is_empty() && self.rng.chance(loopiness) {
+                    let (_, wall) = &closed[self.rng.below(closed.len())];
+                    self.carve(wall);
+                }
+            }
+        }
+    }
+}
+
+/// SplitMix64, small and reproducible for the same seed on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// random number in 0..n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_reproducible_perfect_mazes() {
+        for algorithm in [Algorithm::Backtracker, Algorithm::Prim] {
+            let options = Options {
+                algorithm,
+                width: 20,
+                height: 10,
+                loopiness: 0.0,
+                seed: 7,
+            };
+            let maze = generate(&options);
+            let lines: Vec<&str> = maze.lines().collect();
+
+            assert_eq!(maze, generate(&options));
+            assert_eq!((21, 11), (lines[0].len(), lines.len()));
+            assert_eq!(Some('S'), lines[9].chars().nth(1));
+            assert_eq!(Some('E'), lines[1].chars().nth(19));
+            // a spanning tree of the 10x5 cells has one corridor less than cells
+            let open = maze.chars().filter(|c| *c != '#' && *c != '\n').count();
+            assert_eq!(50 + 49, open);
+        }
+    }
+}
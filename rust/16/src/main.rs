@@ -1,123 +1,211 @@
-use std::{collections::HashSet, env};
+use std::{env, fs, time::Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use cost::Costs;
 use maze::Maze;
+use near::{cheapest_routes, near_optimal_tiles};
 use parser::load_input;
-use pathfinding::prelude::astar_bag;
-use utils::{Direction, Point};
+use paths::{arrows, path_stats};
+use route::Route;
+use search::{search, BestPaths, State};
 
+mod cost;
+mod generator;
 mod maze;
+mod near;
 mod parser;
+mod paths;
+#[cfg(test)]
+mod reference;
+mod route;
+mod search;
+mod svg;
 mod utils;
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-struct Step {
-    previous: Option<Point>,
-    current: Point,
-}
-
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     let file_name = args.get(1).expect("Please provide an input filename");
+    if file_name == "generate" {
+        return generate(&args);
+    }
+    // --paths lists every best path, --svg <file> exports the maze with the best paths
+    let show_paths = args.iter().any(|a| a == "--paths");
+    let svg_file = option(&args, "--svg")?;
+    // --near <delta> marks the points on paths costing at most delta more than
+    // the best path, --routes <k> lists the k cheapest routes
+    // --compare times the search with and without the heuristic
+    // --step-cost <n>, --turn-cost <n>, --u-turn <two-turns|forbidden|n> and
+    // --start-heading <up|right|down|left> change the cost model
+    let costs = costs_from_args(&args)?;
     let input = load_input(file_name)?;
     let maze = input.maze;
-    let start = input.start;
-    let end = input.end;
-
-    println!("From {:?} to {:?}", start, end);
-    println!("{}", maze);
-
-    let result = astar_bag(
-        &Step {
-            previous: None,
-            current: start,
-        },
-        |p| successors(&maze, p),
-        |_| 0,
-        |p| p.current.row == end.row && p.current.col == end.col,
-    );
-
-    if let Some((paths, cost)) = result {
-        println!("Found path with cost {}!", cost);
-
-        let mut points = HashSet::new();
-        for path in paths {
-            for step in path {
-                points.insert(step.current);
-            }
-        }
+    let route = input.route;
+
+    let alternatives = ["--near", "--routes"]
+        .iter()
+        .any(|a| args.contains(&a.to_string()));
+    if alternatives && !route.waypoints.is_empty() {
+        bail!("--near and --routes do not support waypoints");
+    }
+
+    println!("From {:?} to {:?}", route.starts, route.ends);
+    if !route.waypoints.is_empty() {
+        println!("Over {:?}", route.waypoints);
+    }
+
+    if args.iter().any(|a| a == "--compare") {
+        compare(&maze, &route, &costs);
+    }
+
+    let result = search(&maze, &route, &costs, |state, visited| {
+        guide(&maze, &route, &costs, state, visited)
+    });
+
+    if let Some(best) = result {
+        println!("Found path with cost {}!", best.cost);
+
+        let points = best.tiles();
+        println!("{}", maze.marked(&points));
 
         let points_on_best_paths = points.len();
         println!(
             "There are {} points on multiple paths",
             points_on_best_paths
         );
+
+        if show_paths {
+            for (nth, path) in best.paths().enumerate() {
+                let stats = path_stats(&maze, &path, &costs);
+                println!(
+                    "Path {}: {} steps, {} turns, cost {}",
+                    nth + 1,
+                    stats.forward_steps,
+                    stats.turns,
+                    stats.cost
+                );
+                println!("{}", arrows(&path));
+            }
+        }
+        if let Some(svg_file) = svg_file {
+            fs::write(svg_file, svg::render(&maze, &points, &route))?;
+        }
+        if let Some(delta) = option(&args, "--near")? {
+            let delta = delta.parse().context("Invalid cost delta")?;
+            let near =
+                near_optimal_tiles(&maze, &route.starts, &route.ends, &costs, best.cost, delta);
+            let points = near.keys().cloned().collect();
+            println!("{}", maze.marked(&points));
+            println!(
+                "There are {} points on paths costing at most {}",
+                near.len(),
+                best.cost + delta
+            );
+        }
+        if let Some(k) = option(&args, "--routes")? {
+            let k = k.parse().context("Invalid number of routes")?;
+            let routes = cheapest_routes(&maze, &route.starts, &route.ends, &costs, k);
+            for (nth, (path, cost)) in routes.iter().enumerate() {
+                println!("Route {}: cost {}", nth + 1, cost);
+                println!("{}", arrows(path));
+            }
+        }
     } else {
+        println!("{}", maze);
         println!("No path found :(");
     }
 
     Ok(())
 }
 
-fn successors(maze: &Maze, step: &Step) -> Vec<(Step, usize)> {
-    let neighbours: Vec<Step> = step
-        .current
-        .neighbours()
-        .into_iter()
-        .filter(|other| maze.is_walkable(other))
-        .map(|n| Step {
-            previous: Some(step.current.clone()),
-            current: n,
-        })
-        .collect();
-
-    let mut previous_direction = None;
-    if let Some(previous) = step.clone().previous {
-        previous_direction = Some(
-            previous
-                .direction_to(&step.current)
-                .expect("Could not compute direction between fields"),
-        );
+/// Prints a maze generated with
+/// `generate <backtracker|prim> <width> <height> [--loopiness <0 to 1>] [--seed <n>]`
+fn generate(args: &[String]) -> Result<()> {
+    let options = generator::Options {
+        algorithm: args
+            .get(2)
+            .context("Please provide an algorithm")?
+            .parse()?,
+        width: args
+            .get(3)
+            .context("Please provide a width")?
+            .parse()
+            .context("Invalid width")?,
+        height: args
+            .get(4)
+            .context("Please provide a height")?
+            .parse()
+            .context("Invalid height")?,
+        loopiness: option(args, "--loopiness")?
+            .map_or(Ok(0.0), |l| l.parse())
+            .context("Invalid loopiness")?,
+        seed: option(args, "--seed")?
+            .map_or(Ok(0), |s| s.parse())
+            .context("Invalid seed")?,
+    };
+    print!("{}", generator::generate(&options));
+    Ok(())
+}
+
+/// Heuristic of the route, distances say nothing about the cost in mazes with
+/// teleporters so the search goes without one there
+fn guide(maze: &Maze, route: &Route, costs: &Costs, state: &State, visited: usize) -> usize {
+    if maze.has_teleporters() {
+        0
+    } else {
+        route.heuristic(state, visited, costs)
     }
+}
 
-    neighbours
-        .into_iter()
-        .map(|p| {
-            (
-                p.clone(),
-                1 + calculate_turning_costs(
-                    previous_direction.as_ref(),
-                    &p.previous
-                        .unwrap()
-                        .direction_to(&p.current)
-                        .expect("Could not compute direction between fields"),
-                ),
-            )
-        })
-        .collect()
+/// Times the search with and without the heuristic
+fn compare(maze: &Maze, route: &Route, costs: &Costs) {
+    let report = |name: &str, started: Instant, best: Option<BestPaths<Costs>>| match best {
+        Some(best) => println!(
+            "{}: cost {}, {} states expanded in {:?}",
+            name,
+            best.cost,
+            best.expanded,
+            started.elapsed()
+        ),
+        None => println!("{}: no path in {:?}", name, started.elapsed()),
+    };
+
+    let started = Instant::now();
+    let best = search(maze, route, costs, |state, visited| {
+        guide(maze, route, costs, state, visited)
+    });
+    report("A*", started, best);
+
+    let started = Instant::now();
+    let best = search(maze, route, costs, |_, _| 0);
+    report("Dijkstra", started, best);
+}
+
+/// Builds the cost model from the command line, every option defaults to the
+/// rules of the puzzle
+fn costs_from_args(args: &[String]) -> Result<Costs> {
+    let mut costs = Costs::default();
+    if let Some(step) = option(args, "--step-cost")? {
+        costs.step = step.parse().context("Invalid step cost")?;
+    }
+    if let Some(turn) = option(args, "--turn-cost")? {
+        costs.turn = turn.parse().context("Invalid turn cost")?;
+    }
+    if let Some(u_turn) = option(args, "--u-turn")? {
+        costs.u_turn = u_turn.parse()?;
+    }
+    if let Some(heading) = option(args, "--start-heading")? {
+        costs.start_heading = heading.parse()?;
+    }
+    Ok(costs)
 }
 
-fn calculate_turning_costs(
-    current_direction: Option<&Direction>,
-    next_direction: &Direction,
-) -> usize {
-    let current_direction = current_direction.unwrap_or(&Direction::Right);
-    match (current_direction, next_direction) {
-        (Direction::Up, Direction::Up) => 0,
-        (Direction::Up, Direction::Right) => 1000,
-        (Direction::Up, Direction::Down) => 2000,
-        (Direction::Up, Direction::Left) => 1000,
-        (Direction::Right, Direction::Up) => 1000,
-        (Direction::Right, Direction::Right) => 0,
-        (Direction::Right, Direction::Down) => 1000,
-        (Direction::Right, Direction::Left) => 2000,
-        (Direction::Down, Direction::Up) => 2000,
-        (Direction::Down, Direction::Right) => 1000,
-        (Direction::Down, Direction::Down) => 0,
-        (Direction::Down, Direction::Left) => 1000,
-        (Direction::Left, Direction::Up) => 1000,
-        (Direction::Left, Direction::Right) => 2000,
-        (Direction::Left, Direction::Down) => 1000,
-        (Direction::Left, Direction::Left) => 0,
+/// value following the flag, if the flag is given
+fn option<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a String>> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) => args
+            .get(i + 1)
+            .map(Some)
+            .with_context(|| format!("Please provide a value for {}", flag)),
+        None => Ok(None),
     }
 }
@@ -1,22 +1,47 @@
-use std::{fmt::Display, ops::Index};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Index,
+};
 
-use crate::utils::Point;
+use crate::utils::{Direction, Point};
 
 pub struct Maze {
     inner: Vec<Vec<Field>>,
     height: usize,
     width: usize,
+    /// the other end of every teleporter
+    teleports: HashMap<Point, Point>,
 }
 
 impl Maze {
+    /// Creates the maze and links teleporters with the same letter, a
+    /// teleporter without a partner does nothing
     pub fn new(map: Vec<Vec<Field>>) -> Self {
         let height = map.len();
         let width = map.first().expect("Maze should not be empty").len();
 
+        let mut teleporters: HashMap<char, Vec<Point>> = HashMap::new();
+        for (row, fields) in map.iter().enumerate() {
+            for (col, field) in fields.iter().enumerate() {
+                if let Field::Teleporter(c) = field {
+                    teleporters.entry(*c).or_default().push(Point { row, col });
+                }
+            }
+        }
+        let mut teleports = HashMap::new();
+        for points in teleporters.into_values() {
+            if let [a, b] = &points[..] {
+                teleports.insert(a.clone(), b.clone());
+                teleports.insert(b.clone(), a.clone());
+            }
+        }
+
         Maze {
             inner: map,
             height,
             width,
+            teleports,
         }
     }
 
@@ -25,7 +50,50 @@ impl Maze {
     }
 
     pub fn is_walkable(&self, point: &Point) -> bool {
-        self.in_range(point) && matches!(self[point], Field::Space)
+        self.in_range(point) && !matches!(self[point], Field::Wall)
+    }
+
+    /// one-way fields can only be entered moving in their direction
+    pub fn may_enter(&self, point: &Point, direction: &Direction) -> bool {
+        self.is_walkable(point) && self.may_leave(point, direction)
+    }
+
+    /// one-way fields can only be left moving in their direction
+    pub fn may_leave(&self, point: &Point, direction: &Direction) -> bool {
+        match &self[point] {
+            Field::OneWay(one_way) => one_way == direction,
+            _ => true,
+        }
+    }
+
+    /// number of steps stepping onto the field counts as
+    pub fn weight(&self, point: &Point) -> usize {
+        match self[point] {
+            Field::Weighted(weight) => weight,
+            _ => 1,
+        }
+    }
+
+    /// where stepping onto the field leads, if it is a teleporter
+    pub fn teleport_target(&self, point: &Point) -> Option<&Point> {
+        self.teleports.get(point)
+    }
+
+    pub fn has_teleporters(&self) -> bool {
+        !self.teleports.is_empty()
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Displays the maze with the given points drawn as `O`, except for special fields
+    pub fn marked<'a>(&'a self, points: &'a HashSet<Point>) -> MarkedMaze<'a> {
+        MarkedMaze { maze: self, points }
     }
 }
 
@@ -39,6 +107,31 @@ impl Display for Maze {
     }
 }
 
+pub struct MarkedMaze<'a> {
+    maze: &'a Maze,
+    points: &'a HashSet<Point>,
+}
+
+impl Display for MarkedMaze<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (nth_row, row) in self.maze.inner.iter().enumerate() {
+            for (nth_col, field) in row.iter().enumerate() {
+                let point = Point {
+                    row: nth_row,
+                    col: nth_col,
+                };
+                if self.points.contains(&point) && matches!(field, Field::Space) {
+                    write!(f, "O")?;
+                } else {
+                    write!(f, "{}", field)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
 impl Index<&Point> for Maze {
     type Output = Field;
 
@@ -47,16 +140,70 @@ impl Index<&Point> for Maze {
     }
 }
 
+#[derive(Debug, Eq, PartialEq)]
 pub enum Field {
     Wall,
     Space,
+    Start,
+    End,
+    /// waypoints are visited in the order of their numbers, from 1 to 9
+    Waypoint(u8),
+    /// stepping onto a teleporter leads to the other one with the same letter
+    Teleporter(char),
+    OneWay(Direction),
+    /// terrain that costs several steps to walk onto
+    Weighted(usize),
+    /// walkable field with a marker not known to the maze
+    Other(char),
+}
+
+impl Field {
+    /// Fields are written as `#` and `.`, `S` and `E`, waypoints `1` to `9`,
+    /// uppercase teleporters, one-way fields `^>v<` and lowercase weighted
+    /// terrain, `a` counting as 2 steps up to `z` counting as 27.
+    pub fn from_char(c: char) -> Self {
+        match c {
+            '#' => Field::Wall,
+            '.' => Field::Space,
+            'S' => Field::Start,
+            'E' => Field::End,
+            '1'..='9' => Field::Waypoint(c as u8 - b'0'),
+            'A'..='Z' => Field::Teleporter(c),
+            '^' => Field::OneWay(Direction::Up),
+            '>' => Field::OneWay(Direction::Right),
+            'v' => Field::OneWay(Direction::Down),
+            '<' => Field::OneWay(Direction::Left),
+            'a'..='z' => Field::Weighted((c as u8 - b'a') as usize + 2),
+            _ => Field::Other(c),
+        }
+    }
 }
 
 impl Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Field::Wall => write!(f, "#"),
-            Field::Space => write!(f, " "),
+            Field::Space => write!(f, "."),
+            Field::Start => write!(f, "S"),
+            Field::End => write!(f, "E"),
+            Field::Waypoint(n) => write!(f, "{}", n),
+            Field::Teleporter(c) | Field::Other(c) => write!(f, "{}", c),
+            Field::OneWay(Direction::Up) => write!(f, "^"),
+            Field::OneWay(Direction::Right) => write!(f, ">"),
+            Field::OneWay(Direction::Down) => write!(f, "v"),
+            Field::OneWay(Direction::Left) => write!(f, "<"),
+            Field::Weighted(weight) => write!(f, "{}", (b'a' + (*weight - 2) as u8) as char),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_input;
+
+    #[test]
+    fn displays_the_input() {
+        let input = "#######\n#S.A>1#\n#.#c#.#\n#E?.A2#\n#######\n";
+        assert_eq!(input, parse_input(input).unwrap().maze.to_string());
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    iter,
+};
+
+use pathfinding::prelude::yen;
+
+use crate::{
+    cost::CostModel,
+    maze::Maze,
+    search::{predecessors, successors, State, States},
+    utils::{Direction, Point},
+};
+
+/// Tiles on any path costing at most `delta` more than the best path, with the
+/// extra cost of the cheapest path through each tile. Tiles on a best path
+/// have no extra cost.
+pub fn near_optimal_tiles(
+    maze: &Maze,
+    starts: &[Point],
+    ends: &[Point],
+    costs: &impl CostModel,
+    best: usize,
+    delta: usize,
+) -> HashMap<Point, usize> {
+    let states = States::new(maze, 1);
+    let limit = best + delta;
+    let starts: Vec<usize> = starts
+        .iter()
+        .map(|start| {
+            let state = State {
+                position: start.clone(),
+                heading: costs.start_heading(),
+            };
+            states.index(&state, 0)
+        })
+        .collect();
+    // a path ends when it first reaches an end
+    let from_start = dijkstra(states, &starts, limit, |state| {
+        if ends.contains(&state.position) {
+            Vec::new()
+        } else {
+            successors(maze, state, costs)
+        }
+    });
+    let ends_in_any_direction: Vec<usize> = ends
+        .iter()
+        .flat_map(|end| {
+            Direction::ALL.iter().map(|heading| {
+                let state = State {
+                    position: end.clone(),
+                    heading: *heading,
+                };
+                states.index(&state, 0)
+            })
+        })
+        .collect();
+    let to_end = dijkstra(states, &ends_in_any_direction, limit, |state| {
+        predecessors(maze, state, costs)
+            .into_iter()
+            .filter(|(previous, _)| !ends.contains(&previous.position))
+            .collect()
+    });
+
+    let mut tiles = HashMap::new();
+    for (index, (from_start, to_end)) in from_start.iter().zip(&to_end).enumerate() {
+        let Some(cost) = from_start.checked_add(*to_end).filter(|&c| c <= limit) else {
+            continue;
+        };
+        // a state on a teleporter was reached by stepping onto its partner
+        let position = states.state(index).0.position;
+        let entered = maze.teleport_target(&position).cloned();
+        for position in iter::once(position).chain(entered) {
+            let extra = tiles.entry(position).or_insert(usize::MAX);
+            *extra = (*extra).min(cost - best);
+        }
+    }
+    tiles
+}
+
+/// Cheapest cost of every state from the sources, usize::MAX for states
+/// not reached within the limit
+fn dijkstra(
+    states: States,
+    sources: &[usize],
+    limit: usize,
+    neighbours: impl Fn(&State) -> Vec<(State, usize)>,
+) -> Vec<usize> {
+    let mut cost_to = vec![usize::MAX; states.count()];
+    let mut queue = BinaryHeap::new();
+    for &source in sources {
+        cost_to[source] = 0;
+        queue.push(Reverse((0, source)));
+    }
+
+    while let Some(Reverse((cost, index))) = queue.pop() {
+        if cost > cost_to[index] {
+            continue;
+        }
+        for (next, step_cost) in neighbours(&states.state(index).0) {
+            let next_index = states.index(&next, 0);
+            let next_cost = cost + step_cost;
+            if next_cost <= limit && next_cost < cost_to[next_index] {
+                cost_to[next_index] = next_cost;
+                queue.push(Reverse((next_cost, next_index)));
+            }
+        }
+    }
+    cost_to
+}
+
+/// The k cheapest distinct routes from any start to any end, cheapest first
+pub fn cheapest_routes(
+    maze: &Maze,
+    starts: &[Point],
+    ends: &[Point],
+    costs: &impl CostModel,
+    k: usize,
+) -> Vec<(Vec<State>, usize)> {
+    // the k cheapest routes overall are among the k cheapest of each start
+    let mut routes: Vec<(Vec<State>, usize)> = starts
+        .iter()
+        .flat_map(|start| {
+            let start = State {
+                position: start.clone(),
+                heading: costs.start_heading(),
+            };
+            yen(
+                &start,
+                |state| successors(maze, state, costs),
+                |state| ends.contains(&state.position),
+                k,
+            )
+        })
+        .collect();
+    routes.sort_by_key(|(_, cost)| *cost);
+    routes.truncate(k);
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cost::Costs, parser::parse_input, search::search};
+
+    use super::*;
+
+    #[test]
+    fn finds_detours_around_the_wall() {
+        let input = parse_input("#######\n#.....#\n#S.#.E#\n#.....#\n#######").unwrap();
+        let (maze, route) = (&input.maze, &input.route);
+        let (starts, ends) = (&route.starts, &route.ends);
+        let costs = Costs::default();
+        let best = search(maze, route, &costs, |s, v| route.heuristic(s, v, &costs)).unwrap();
+
+        let optimal = near_optimal_tiles(maze, starts, ends, &costs, best.cost, 0);
+        assert_eq!(best.tiles(), optimal.keys().cloned().collect());
+
+        // only the field right of the wall needs another turn
+        let near = near_optimal_tiles(maze, starts, ends, &costs, best.cost, 1000);
+        assert_eq!(14, near.len());
+        assert_eq!(Some(&1000), near.get(&Point { row: 2, col: 4 }));
+
+        let routes = cheapest_routes(maze, starts, ends, &costs, 6);
+        let route_costs: Vec<usize> = routes.iter().map(|(_, cost)| *cost).collect();
+        assert_eq!(vec![3006, 3006, 3006, 3006, 4006, 4006], route_costs);
+    }
+}
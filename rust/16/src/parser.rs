@@ -1,55 +1,73 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+    collections::{BTreeMap, HashMap},
+    fs,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
 
 use crate::{
     maze::{Field, Maze},
+    route::Route,
     utils::Point,
 };
 
 pub fn load_input(path: &str) -> Result<Input> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    let input = fs::read_to_string(path)?;
+    parse_input(&input)
+}
 
+/// Parses a maze of `#` walls with at least one `S` start and one `E` end
+/// field, see `Field::from_char` for all fields. The digits 1 to 9 mark
+/// waypoints to visit in this order, teleporters come in pairs.
+pub fn parse_input(input: &str) -> Result<Input> {
     let mut map: Vec<Vec<Field>> = Vec::new();
-    let mut start = None;
-    let mut end = None;
+    let mut starts = Vec::new();
+    let mut ends = Vec::new();
+    let mut waypoints = BTreeMap::new();
+    let mut teleporters: HashMap<char, usize> = HashMap::new();
 
-    for (nth_row, line) in reader.lines().map_while(Result::ok).enumerate() {
+    for (nth_row, line) in input.lines().enumerate() {
         let mut row = Vec::new();
         for (nth_col, c) in line.chars().enumerate() {
-            row.push(match c {
-                '#' => Field::Wall,
-                _ => Field::Space,
-            });
-
-            if c == 'S' {
-                start = Some(Point {
-                    row: nth_row,
-                    col: nth_col,
-                });
-            } else if c == 'E' {
-                end = Some(Point {
-                    row: nth_row,
-                    col: nth_col,
-                });
+            let point = Point {
+                row: nth_row,
+                col: nth_col,
+            };
+            let field = Field::from_char(c);
+            match field {
+                Field::Start => starts.push(point),
+                Field::End => ends.push(point),
+                Field::Waypoint(n) if waypoints.insert(n, point).is_some() => {
+                    bail!("Waypoint {} appears more than once", n);
+                }
+                Field::Teleporter(c) => *teleporters.entry(c).or_default() += 1,
+                _ => {}
             }
+            row.push(field);
         }
         map.push(row);
     }
 
+    if starts.is_empty() {
+        bail!("Could not find start");
+    }
+    if ends.is_empty() {
+        bail!("Could not find end");
+    }
+    if let Some((c, _)) = teleporters.iter().find(|(_, &count)| count != 2) {
+        bail!("Teleporter {} needs exactly two fields", c);
+    }
     Ok(Input {
         maze: Maze::new(map),
-        start: start.context("Could not find start")?,
-        end: end.context("Could not find end")?,
+        route: Route {
+            starts,
+            waypoints: waypoints.into_values().collect(),
+            ends,
+        },
     })
 }
 
 pub struct Input {
     pub maze: Maze,
-    pub start: Point,
-    pub end: Point,
+    pub route: Route,
 }
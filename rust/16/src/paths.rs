@@ -0,0 +1,90 @@
+use crate::{
+    cost::CostModel,
+    maze::Maze,
+    search::{successors, State},
+    utils::Direction,
+};
+
+/// Breakdown of the cost of a single path through the maze
+#[derive(Debug, Eq, PartialEq)]
+pub struct PathStats {
+    pub forward_steps: usize,
+    /// number of 90° turns, turning around counts as two
+    pub turns: usize,
+    pub cost: usize,
+}
+
+/// Counts the steps and turns of a path of states, each a single step from
+/// the previous one
+pub fn path_stats(maze: &Maze, path: &[State], costs: &impl CostModel) -> PathStats {
+    let mut turns = 0;
+    let mut cost = 0;
+
+    for pair in path.windows(2) {
+        turns += pair[0].heading.quarter_turns_to(&pair[1].heading);
+        cost += successors(maze, &pair[0], costs)
+            .into_iter()
+            .find(|(next, _)| *next == pair[1])
+            .map(|(_, cost)| cost)
+            .expect("Path should only contain allowed steps");
+    }
+
+    PathStats {
+        forward_steps: path.len().saturating_sub(1),
+        turns,
+        cost,
+    }
+}
+
+/// Draws the steps of the path as a line of arrows, e.g. `>>^^>`
+pub fn arrows(path: &[State]) -> String {
+    path.iter()
+        .skip(1)
+        .map(|state| match state.heading {
+            Direction::Up => '^',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cost::Costs, maze::Field, utils::Point};
+
+    use super::*;
+
+    #[test]
+    fn counts_steps_and_turns() {
+        let maze = Maze::new(
+            ["####", "#.a#", "#..#", "#..#", "####"]
+                .iter()
+                .map(|line| line.chars().map(Field::from_char).collect())
+                .collect(),
+        );
+        let state = |row, col, heading| State {
+            position: Point { row, col },
+            heading,
+        };
+        // right, up onto the weighted field, up, left and back down
+        let path = [
+            state(3, 1, Direction::Right),
+            state(3, 2, Direction::Right),
+            state(2, 2, Direction::Up),
+            state(1, 2, Direction::Up),
+            state(1, 1, Direction::Left),
+            state(2, 1, Direction::Down),
+        ];
+
+        assert_eq!(">^^<v", arrows(&path));
+        assert_eq!(
+            PathStats {
+                forward_steps: 5,
+                turns: 3,
+                cost: 3006
+            },
+            path_stats(&maze, &path, &Costs::default())
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Brute force solver to check the search against on small mazes
+
+use std::collections::HashSet;
+
+use crate::{
+    cost::CostModel,
+    maze::Maze,
+    search::{successors, State},
+    utils::Point,
+};
+
+/// Cost, number and tiles of the best paths
+pub struct Reference {
+    pub cost: usize,
+    pub paths: usize,
+    pub tiles: HashSet<Point>,
+}
+
+/// Tries every path that does not visit a field twice
+pub fn solve(maze: &Maze, start: &Point, end: &Point, costs: &impl CostModel) -> Option<Reference> {
+    let mut best: Option<Reference> = None;
+    let mut path = vec![start.clone()];
+    let state = State {
+        position: start.clone(),
+        heading: costs.start_heading(),
+    };
+    walk(maze, end, costs, &state, 0, &mut path, &mut best);
+    best
+}
+
+fn walk(
+    maze: &Maze,
+    end: &Point,
+    costs: &impl CostModel,
+    state: &State,
+    cost: usize,
+    path: &mut Vec<Point>,
+    best: &mut Option<Reference>,
+) {
+    if best.as_ref().is_some_and(|best| cost > best.cost) {
+        return;
+    }
+    if state.position == *end {
+        match best {
+            Some(best) if best.cost == cost => {
+                best.paths += 1;
+                best.tiles.extend(path.iter().cloned());
+            }
+            _ => {
+                *best = Some(Reference {
+                    cost,
+                    paths: 1,
+                    tiles: path.iter().cloned().collect(),
+                })
+            }
+        }
+        return;
+    }
+
+    for (next, step_cost) in successors(maze, state, costs) {
+        if path.contains(&next.position) {
+            continue;
+        }
+        path.push(next.position.clone());
+        walk(maze, end, costs, &next, cost + step_cost, path, best);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::{
+        cost::Costs,
+        generator::{generate, Algorithm, Options},
+        parser::parse_input,
+        search::search,
+    };
+
+    use super::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(50))]
+        #[test]
+        fn search_finds_all_best_paths(
+            prim in any::<bool>(),
+            width in 3usize..12,
+            height in 3usize..12,
+            loopiness in 0.0..=1.0,
+            seed in any::<u64>(),
+        ) {
+            let options = Options {
+                algorithm: if prim { Algorithm::Prim } else { Algorithm::Backtracker },
+                width,
+                height,
+                loopiness,
+                seed,
+            };
+            let input = parse_input(&generate(&options)).unwrap();
+            let costs = Costs::default();
+
+            let route = &input.route;
+
+            let expected = solve(&input.maze, &route.starts[0], &route.ends[0], &costs).unwrap();
+            let best = search(&input.maze, route, &costs, |state, visited| {
+                route.heuristic(state, visited, &costs)
+            })
+            .unwrap();
+
+            prop_assert_eq!(expected.cost, best.cost);
+            prop_assert_eq!(expected.paths, best.paths().count());
+            prop_assert_eq!(expected.tiles, best.tiles());
+        }
+    }
+}
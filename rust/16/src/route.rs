@@ -0,0 +1,54 @@
+use crate::{
+    cost::CostModel,
+    search::{heuristic, State},
+    utils::Point,
+};
+
+/// Where paths may start, the waypoints they have to visit in order and
+/// where they may end
+pub struct Route {
+    pub starts: Vec<Point>,
+    pub waypoints: Vec<Point>,
+    pub ends: Vec<Point>,
+}
+
+impl Route {
+    /// number of waypoints visited after stepping onto the position
+    pub fn advance(&self, visited: usize, position: &Point) -> usize {
+        match self.waypoints.get(visited) {
+            Some(waypoint) if waypoint == position => visited + 1,
+            _ => visited,
+        }
+    }
+
+    pub fn is_goal(&self, visited: usize, position: &Point) -> bool {
+        visited == self.waypoints.len() && self.ends.contains(position)
+    }
+
+    /// Lower bound for the rest of the route: the search heuristic to the next
+    /// waypoint, the steps between the remaining waypoints and the steps from the
+    /// last one to the nearest end. Teleporters make it overestimate.
+    pub fn heuristic(&self, state: &State, visited: usize, costs: &impl CostModel) -> usize {
+        let remaining = &self.waypoints[visited..];
+        let Some(next) = remaining.first() else {
+            return self
+                .ends
+                .iter()
+                .map(|end| heuristic(state, end, costs))
+                .min()
+                .unwrap_or(0);
+        };
+
+        let distance = |a: &Point, b: &Point| a.row.abs_diff(b.row) + a.col.abs_diff(b.col);
+        let between: usize = remaining.windows(2).map(|w| distance(&w[0], &w[1])).sum();
+        let last = remaining.last().unwrap_or(next);
+        let to_end = self
+            .ends
+            .iter()
+            .map(|end| distance(last, end))
+            .min()
+            .unwrap_or(0);
+
+        heuristic(state, next, costs) + (between + to_end) * costs.step_cost()
+    }
+}
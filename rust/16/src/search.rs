@@ -0,0 +1,397 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    iter,
+};
+
+use crate::{
+    cost::{CostModel, UTurn},
+    maze::Maze,
+    route::Route,
+    utils::{Direction, Point},
+};
+
+/// Position in the maze together with the direction the reindeer faces
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct State {
+    pub position: Point,
+    pub heading: Direction,
+}
+
+/// Result of an A* search over all (position, heading) states and the number
+/// of waypoints visited, which keeps the cost of every reached state to recover
+/// all best paths afterwards.
+pub struct BestPaths<'a, C> {
+    maze: &'a Maze,
+    states: States,
+    route: &'a Route,
+    costs: &'a C,
+    starts: Vec<usize>,
+    /// end states reached with the lowest cost
+    goals: Vec<usize>,
+    /// lowest known cost of every state, indexed by `States::index`, usize::MAX if not reached
+    cost_to: Vec<usize>,
+    pub cost: usize,
+    /// number of states taken from the queue
+    pub expanded: usize,
+}
+
+/// Searches all best paths from any start over all waypoints to any end. The
+/// heuristic gets the state and the number of visited waypoints and has to be
+/// consistent, e.g. `Route::heuristic` or no heuristic at all with `|_, _| 0`.
+pub fn search<'a, C: CostModel>(
+    maze: &'a Maze,
+    route: &'a Route,
+    costs: &'a C,
+    heuristic: impl Fn(&State, usize) -> usize,
+) -> Option<BestPaths<'a, C>> {
+    let states = States::new(maze, route.waypoints.len() + 1);
+    let mut result = BestPaths {
+        maze,
+        states,
+        route,
+        costs,
+        starts: Vec::new(),
+        goals: Vec::new(),
+        cost_to: vec![usize::MAX; states.count()],
+        cost: 0,
+        expanded: 0,
+    };
+
+    let mut queue = BinaryHeap::new();
+    for start in &route.starts {
+        let state = State {
+            position: start.clone(),
+            heading: costs.start_heading(),
+        };
+        let visited = route.advance(0, start);
+        let index = states.index(&state, visited);
+        result.starts.push(index);
+        result.cost_to[index] = 0;
+        queue.push(Reverse((heuristic(&state, visited), 0, index)));
+    }
+    let mut best = None;
+
+    while let Some(Reverse((estimate, cost, index))) = queue.pop() {
+        if best.is_some_and(|best| estimate > best) {
+            break;
+        }
+        if cost > result.cost_to[index] {
+            continue;
+        }
+        result.expanded += 1;
+
+        let (state, visited) = states.state(index);
+        if route.is_goal(visited, &state.position) {
+            best = Some(cost);
+            result.goals.push(index);
+            continue;
+        }
+        for (next, step_cost) in successors(maze, &state, costs) {
+            let next_visited = route.advance(visited, &next.position);
+            let next_index = states.index(&next, next_visited);
+            let next_cost = cost + step_cost;
+            if next_cost < result.cost_to[next_index] {
+                result.cost_to[next_index] = next_cost;
+                queue.push(Reverse((
+                    next_cost + heuristic(&next, next_visited),
+                    next_cost,
+                    next_index,
+                )));
+            }
+        }
+    }
+
+    result.cost = best?;
+    Some(result)
+}
+
+/// States reachable with a single step and the cost of turning and stepping.
+/// Stepping onto a teleporter leads to its partner, keeping the heading.
+pub fn successors(maze: &Maze, state: &State, costs: &impl CostModel) -> Vec<(State, usize)> {
+    Direction::ALL
+        .iter()
+        .filter_map(|direction| {
+            if !maze.may_leave(&state.position, direction) {
+                return None;
+            }
+            let stepped = state
+                .position
+                .next_in_direction(direction)
+                .filter(|p| maze.may_enter(p, direction))?;
+            let cost = costs.move_cost(&state.heading, direction)?
+                + (maze.weight(&stepped) - 1) * costs.step_cost();
+            let position = maze.teleport_target(&stepped).cloned().unwrap_or(stepped);
+            Some((
+                State {
+                    position,
+                    heading: *direction,
+                },
+                cost,
+            ))
+        })
+        .collect()
+}
+
+/// States leading to this state with a single step and the cost of that step,
+/// the reverse of `successors`
+pub fn predecessors(maze: &Maze, state: &State, costs: &impl CostModel) -> Vec<(State, usize)> {
+    // a teleporter is only reached by stepping onto its partner
+    let stepped = maze
+        .teleport_target(&state.position)
+        .unwrap_or(&state.position);
+    let Some(position) = stepped
+        .next_in_direction(&state.heading.opposite())
+        .filter(|p| maze.is_walkable(p) && maze.may_leave(p, &state.heading))
+        .filter(|_| maze.may_enter(stepped, &state.heading))
+    else {
+        return Vec::new();
+    };
+    let weight_cost = (maze.weight(stepped) - 1) * costs.step_cost();
+
+    Direction::ALL
+        .iter()
+        .filter_map(|heading| {
+            let cost = costs.move_cost(heading, &state.heading)? + weight_cost;
+            Some((
+                State {
+                    position: position.clone(),
+                    heading: *heading,
+                },
+                cost,
+            ))
+        })
+        .collect()
+}
+
+/// Lower bound for the cost to the end: every remaining step, plus the turns
+/// needed to face each direction the end lies in. Consistent for any cost model.
+pub fn heuristic(state: &State, end: &Point, costs: &impl CostModel) -> usize {
+    let position = &state.position;
+    let steps = position.row.abs_diff(end.row) + position.col.abs_diff(end.col);
+
+    let quarter = costs.turn_cost();
+    let half = match costs.u_turn() {
+        UTurn::Fixed(cost) => cost.min(2 * quarter),
+        UTurn::TwoTurns | UTurn::Forbidden => 2 * quarter,
+    };
+    let turning = |direction: &Direction| match state.heading.quarter_turns_to(direction) {
+        0 => 0,
+        1 => quarter,
+        _ => half,
+    };
+    let vertical = position.direction_to(&Point {
+        row: end.row,
+        col: position.col,
+    });
+    let horizontal = position.direction_to(&Point {
+        row: position.row,
+        col: end.col,
+    });
+    let turns = match (vertical, horizontal) {
+        (Some(vertical), Some(horizontal)) => {
+            turning(&vertical).min(turning(&horizontal)) + quarter
+        }
+        (Some(direction), None) | (None, Some(direction)) => turning(&direction),
+        (None, None) => 0,
+    };
+
+    steps * costs.step_cost() + turns
+}
+
+/// Numbers the states of a maze, to keep data about them in flat vectors. Each
+/// layer holds all states once, e.g. one layer per number of visited waypoints.
+#[derive(Clone, Copy)]
+pub struct States {
+    width: usize,
+    height: usize,
+    layers: usize,
+}
+
+impl States {
+    pub fn new(maze: &Maze, layers: usize) -> Self {
+        States {
+            width: maze.width(),
+            height: maze.height(),
+            layers,
+        }
+    }
+
+    fn per_layer(&self) -> usize {
+        self.width * self.height * 4
+    }
+
+    pub fn count(&self) -> usize {
+        self.per_layer() * self.layers
+    }
+
+    pub fn index(&self, state: &State, layer: usize) -> usize {
+        layer * self.per_layer()
+            + (state.position.row * self.width + state.position.col) * 4
+            + state.heading.index()
+    }
+
+    pub fn state(&self, index: usize) -> (State, usize) {
+        let (layer, index) = (index / self.per_layer(), index % self.per_layer());
+        let field = index / 4;
+        let state = State {
+            position: Point {
+                row: field / self.width,
+                col: field % self.width,
+            },
+            heading: Direction::ALL[index % 4],
+        };
+        (state, layer)
+    }
+}
+
+impl<C: CostModel> BestPaths<'_, C> {
+    /// Previous states on a best path. A state reached with cost c from a
+    /// state with cost p by a move costing m lies on a best path if p + m = c.
+    fn predecessors(&self, index: usize) -> Vec<usize> {
+        let (state, visited) = self.states.state(index);
+        // stepping onto the next waypoint counts it as visited
+        let layers: Vec<usize> = (visited.saturating_sub(1)..=visited)
+            .filter(|&layer| self.route.advance(layer, &state.position) == visited)
+            .collect();
+
+        predecessors(self.maze, &state, self.costs)
+            .into_iter()
+            .flat_map(|(previous, step_cost)| {
+                layers.iter().filter_map(move |&layer| {
+                    let previous = self.states.index(&previous, layer);
+                    let cost = self.cost_to[previous];
+                    (cost != usize::MAX && cost + step_cost == self.cost_to[index])
+                        .then_some(previous)
+                })
+            })
+            .collect()
+    }
+
+    /// all points on any best path
+    pub fn tiles(&self) -> HashSet<Point> {
+        let mut seen: HashSet<usize> = self.goals.iter().copied().collect();
+        let mut stack = self.goals.clone();
+        while let Some(index) = stack.pop() {
+            for previous in self.predecessors(index) {
+                if seen.insert(previous) {
+                    stack.push(previous);
+                }
+            }
+        }
+        // a state on a teleporter was reached by stepping onto its partner
+        seen.into_iter()
+            .flat_map(|i| {
+                let position = self.states.state(i).0.position;
+                let entered = self.maze.teleport_target(&position).cloned();
+                iter::once(position).chain(entered)
+            })
+            .collect()
+    }
+
+    /// Enumerates the best paths one by one, there may be very many of them
+    pub fn paths(&self) -> Paths<'_, C> {
+        Paths {
+            best: self,
+            stack: self.goals.iter().map(|&goal| (goal, 0)).collect(),
+            path: Vec::new(),
+        }
+    }
+}
+
+/// Depth first walk from the end states back to the starts
+pub struct Paths<'a, C> {
+    best: &'a BestPaths<'a, C>,
+    /// states still to visit with their distance from the end
+    stack: Vec<(usize, usize)>,
+    /// states from the end to the current state
+    path: Vec<usize>,
+}
+
+impl<C: CostModel> Iterator for Paths<'_, C> {
+    type Item = Vec<State>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((index, depth)) = self.stack.pop() {
+            self.path.truncate(depth);
+            self.path.push(index);
+            if self.best.starts.contains(&index) {
+                return Some(
+                    self.path
+                        .iter()
+                        .rev()
+                        .map(|&i| self.best.states.state(i).0)
+                        .collect(),
+                );
+            }
+            for previous in self.best.predecessors(index) {
+                self.stack.push((previous, depth + 1));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cost::Costs, parser::parse_input};
+
+    use super::*;
+
+    #[test]
+    fn heuristic_does_not_change_best_paths() {
+        // the wall can be passed above or below, turning before or after one step
+        let input = parse_input("#######\n#.....#\n#S.#.E#\n#.....#\n#######").unwrap();
+        let (maze, route) = (&input.maze, &input.route);
+        let costs = Costs::default();
+
+        let guided = search(maze, route, &costs, |s, visited| {
+            route.heuristic(s, visited, &costs)
+        })
+        .expect("Path exists");
+        let unguided = search(maze, route, &costs, |_, _| 0).expect("Path exists");
+
+        assert_eq!(3006, guided.cost);
+        assert_eq!(unguided.cost, guided.cost);
+        assert_eq!(13, guided.tiles().len());
+        assert_eq!(unguided.tiles(), guided.tiles());
+        assert_eq!(4, guided.paths().count());
+        assert!(guided.expanded <= unguided.expanded);
+    }
+
+    #[test]
+    fn visits_waypoints_in_order_from_any_start_to_any_end() {
+        // the lower start is closer to waypoint 1, from there turning around
+        // leads over waypoint 2 to the upper end without passing the lower one
+        let input = parse_input("#########\n#S..2..E#\n#.#####.#\n#S.1...E#\n#########").unwrap();
+        let (maze, route) = (&input.maze, &input.route);
+        let costs = Costs::default();
+
+        let best = search(maze, route, &costs, |s, visited| {
+            route.heuristic(s, visited, &costs)
+        })
+        .expect("Path exists");
+
+        let paths: Vec<Vec<State>> = best.paths().collect();
+        assert_eq!(4012, best.cost);
+        assert_eq!(1, paths.len());
+        assert_eq!(">><<^^>>>>>>", crate::paths::arrows(&paths[0]));
+    }
+
+    #[test]
+    fn follows_teleporters_and_one_way_fields() {
+        // the one-way field blocks the short way, the teleporter leads from
+        // the dead end below the start to the end
+        let input = parse_input("#########\n#S.<..E.#\n#.####.##\n#A#..A..#\n#########").unwrap();
+        let (maze, route) = (&input.maze, &input.route);
+        let costs = Costs::default();
+
+        let best = search(maze, route, &costs, |_, _| 0).expect("Path exists");
+        let paths: Vec<Vec<State>> = best.paths().collect();
+
+        assert_eq!(1, paths.len());
+        assert_eq!("vv>^^", crate::paths::arrows(&paths[0]));
+        assert_eq!(3005, best.cost);
+        assert!(best.tiles().contains(&Point { row: 3, col: 1 }));
+    }
+}
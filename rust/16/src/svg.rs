@@ -0,0 +1,59 @@
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+    maze::{Field, Maze},
+    route::Route,
+    utils::Point,
+};
+
+/// Size of a single field in pixels
+const FIELD_SIZE: usize = 10;
+
+/// Renders the maze as SVG, with walls in grey, the points of the best paths
+/// in orange, the starts in green, the waypoints in blue and the ends in red.
+/// Teleporters and weighted terrain off the best paths are purple and tan.
+pub fn render(maze: &Maze, best: &HashSet<Point>, route: &Route) -> String {
+    let mut svg = String::new();
+    let (width, height) = (maze.width() * FIELD_SIZE, maze.height() * FIELD_SIZE);
+    // writing to a string never fails
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="white"/>"#
+    );
+
+    for row in 0..maze.height() {
+        for col in 0..maze.width() {
+            let point = Point { row, col };
+            let fill = if route.starts.contains(&point) {
+                "green"
+            } else if route.waypoints.contains(&point) {
+                "royalblue"
+            } else if route.ends.contains(&point) {
+                "red"
+            } else if best.contains(&point) {
+                "orange"
+            } else if matches!(maze[&point], Field::Wall) {
+                "dimgrey"
+            } else if matches!(maze[&point], Field::Teleporter(_)) {
+                "purple"
+            } else if matches!(maze[&point], Field::Weighted(_)) {
+                "tan"
+            } else {
+                continue;
+            };
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{FIELD_SIZE}" height="{FIELD_SIZE}" fill="{fill}"/>"#,
+                col * FIELD_SIZE,
+                row * FIELD_SIZE,
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -1,3 +1,7 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Point {
     pub row: usize,
@@ -5,30 +9,26 @@ pub struct Point {
 }
 
 impl Point {
-    pub fn neighbours(&self) -> Vec<Point> {
-        let mut neighbours = vec![
-            Point {
-                row: self.row + 1,
-                col: self.col,
-            },
-            Point {
+    /// neighbour in the given direction, None at the top or left border
+    pub fn next_in_direction(&self, direction: &Direction) -> Option<Point> {
+        match direction {
+            Direction::Up => self
+                .row
+                .checked_sub(1)
+                .map(|row| Point { row, col: self.col }),
+            Direction::Right => Some(Point {
                 row: self.row,
                 col: self.col + 1,
-            },
-        ];
-        if self.row > 0 {
-            neighbours.push(Point {
-                row: self.row - 1,
+            }),
+            Direction::Down => Some(Point {
+                row: self.row + 1,
                 col: self.col,
-            });
+            }),
+            Direction::Left => self
+                .col
+                .checked_sub(1)
+                .map(|col| Point { row: self.row, col }),
         }
-        if self.col > 0 {
-            neighbours.push(Point {
-                row: self.row,
-                col: self.col - 1,
-            });
-        }
-        neighbours
     }
 
     pub fn direction_to(&self, to: &Point) -> Option<Direction> {
@@ -49,9 +49,56 @@ impl Point {
     }
 }
 
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 pub enum Direction {
     Up,
     Right,
     Down,
     Left,
 }
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    /// position in `Direction::ALL`, clockwise from up
+    pub fn index(&self) -> usize {
+        match self {
+            Direction::Up => 0,
+            Direction::Right => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        Direction::ALL[(self.index() + 2) % 4]
+    }
+
+    /// number of 90° turns needed to face the other direction
+    pub fn quarter_turns_to(&self, other: &Direction) -> usize {
+        match (self.index() + 4 - other.index()) % 4 {
+            0 => 0,
+            2 => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "up" => Ok(Direction::Up),
+            "right" => Ok(Direction::Right),
+            "down" => Ok(Direction::Down),
+            "left" => Ok(Direction::Left),
+            _ => bail!("Unknown direction {}, expected up, right, down or left", s),
+        }
+    }
+}
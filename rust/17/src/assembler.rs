@@ -0,0 +1,395 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    instructions::{Combo, Instruction, Literal, Operand, Register},
+    isa::{Isa, OperandKind, ReservedCombo},
+    program::Program,
+    state::RegisterState,
+};
+
+/// Assembles mnemonic source into a program and its initial registers.
+///
+/// Each line holds at most one instruction, optionally preceded by a label:
+/// ```text
+/// .register A 729   ; initial register value, defaults to 0
+/// loop: adv 1       ; same as `adv(1)`, the disassembler output
+///       out A
+///       jnz loop    ; labels resolve to the pc of the next instruction
+/// ```
+/// `;` and `#` start comments. Combo operands are `0`-`3`, a register or the
+/// reserved `7`, `bxc` may omit its (ignored) operand. Extensions of the instruction set are
+/// available by their mnemonic, register D only if the set maps combo operand 7 to it.
+pub fn assemble(source: &str, isa: &Isa) -> Result<(Program, RegisterState), AssembleError> {
+    let mut lines = Vec::new();
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut registers = RegisterState::default();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = Line::tokenize(index + 1, text)?;
+        let mut tokens = line.tokens.as_slice();
+
+        if let Some(first) = tokens.first() {
+            if let Some(label) = first.text.strip_suffix(':') {
+                if !is_identifier(label) {
+                    return Err(first.error(format!("Invalid label name '{}'", label)));
+                }
+                let pc = lines.len() * 2;
+                if labels.insert(label.to_string(), pc).is_some() {
+                    return Err(first.error(format!("Duplicate label '{}'", label)));
+                }
+                tokens = &tokens[1..];
+            }
+        }
+
+        match tokens.first() {
+            None => {}
+            Some(directive) if directive.text.starts_with('.') => {
+                parse_directive(directive, &tokens[1..], isa, &mut registers)?
+            }
+            Some(_) => lines.push(tokens.to_vec()),
+        }
+    }
+
+    let instructions = lines
+        .iter()
+        .map(|tokens| parse_instruction(tokens, &labels, isa))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((Program::new(instructions), registers))
+}
+
+#[derive(Debug)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: String) -> AssembleError {
+        AssembleError {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+}
+
+struct Line {
+    tokens: Vec<Token>,
+}
+
+impl Line {
+    /// splits on whitespace and parentheses, so `adv(3)` and `adv 3` yield the same tokens
+    fn tokenize(line: usize, text: &str) -> Result<Self, AssembleError> {
+        let mut tokens = Vec::new();
+        let mut current: Option<Token> = None;
+        let mut open_paren: Option<usize> = None;
+
+        for (index, c) in text.chars().enumerate() {
+            let column = index + 1;
+            if c == ';' || c == '#' {
+                break;
+            }
+            if c.is_whitespace() || c == '(' || c == ')' {
+                tokens.extend(current.take());
+                match (c, open_paren) {
+                    ('(', None) => open_paren = Some(column),
+                    (')', Some(_)) => open_paren = None,
+                    ('(', Some(_)) | (')', None) => {
+                        return Err(AssembleError {
+                            line,
+                            column,
+                            message: format!("Unexpected '{}'", c),
+                        })
+                    }
+                    _ => {}
+                }
+            } else {
+                current
+                    .get_or_insert_with(|| Token {
+                        text: String::new(),
+                        line,
+                        column,
+                    })
+                    .text
+                    .push(c);
+                // a label ends at its colon, even without a space before the instruction
+                if c == ':' {
+                    tokens.extend(current.take());
+                }
+            }
+        }
+        tokens.extend(current);
+
+        if let Some(column) = open_paren {
+            return Err(AssembleError {
+                line,
+                column,
+                message: "Unclosed '('".to_string(),
+            });
+        }
+
+        Ok(Line { tokens })
+    }
+}
+
+fn parse_directive(
+    directive: &Token,
+    arguments: &[Token],
+    isa: &Isa,
+    registers: &mut RegisterState,
+) -> Result<(), AssembleError> {
+    if directive.text != ".register" {
+        return Err(directive.error(format!("Unknown directive '{}'", directive.text)));
+    }
+    let [register, value] = arguments else {
+        return Err(directive.error("Expected `.register <A|B|C> <value>`".to_string()));
+    };
+    let value = value
+        .text
+        .parse()
+        .map_err(|_| value.error(format!("Invalid register value '{}'", value.text)))?;
+
+    match parse_register(register, isa)? {
+        Some(Register::A) => registers.a = value,
+        Some(Register::B) => registers.b = value,
+        Some(Register::C) => registers.c = value,
+        Some(Register::D) => registers.d = value,
+        None => {
+            return Err(register.error(format!("Unknown register '{}'", register.text)));
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_instruction(
+    tokens: &[Token],
+    labels: &HashMap<String, usize>,
+    isa: &Isa,
+) -> Result<Instruction, AssembleError> {
+    let mnemonic = &tokens[0];
+    let operand = tokens.get(1);
+    if let Some(extra) = tokens.get(2) {
+        return Err(extra.error(format!("Unexpected '{}'", extra.text)));
+    }
+    let missing_operand = || mnemonic.error(format!("Missing operand for {}", mnemonic.text));
+
+    let combo = || parse_combo(operand.ok_or_else(missing_operand)?, isa);
+    let instruction = match mnemonic.text.to_lowercase().as_str() {
+        "adv" => Instruction::Adv(combo()?),
+        "bxl" => Instruction::Bxl(parse_literal(operand.ok_or_else(missing_operand)?)?),
+        "bst" => Instruction::Bst(combo()?),
+        "jnz" => Instruction::Jnz(parse_jump_target(
+            operand.ok_or_else(missing_operand)?,
+            labels,
+        )?),
+        "bxc" => Instruction::Bxc(operand.map_or(Ok(Literal(0)), parse_literal)?),
+        "out" => Instruction::Out(combo()?),
+        "bdv" => Instruction::Bdv(combo()?),
+        "cdv" => Instruction::Cdv(combo()?),
+        text => {
+            let extension = isa
+                .extension(text)
+                .ok_or_else(|| mnemonic.error(format!("Unknown mnemonic '{}'", mnemonic.text)))?;
+            let operand = match extension.operand {
+                OperandKind::Combo => Operand::Combo(combo()?),
+                OperandKind::Literal => {
+                    Operand::Literal(parse_literal(operand.ok_or_else(missing_operand)?)?)
+                }
+                OperandKind::Ignored => {
+                    Operand::Literal(operand.map_or(Ok(Literal(0)), parse_literal)?)
+                }
+            };
+            Instruction::Extended(extension, operand)
+        }
+    };
+
+    Ok(instruction)
+}
+
+fn parse_combo(token: &Token, isa: &Isa) -> Result<Combo, AssembleError> {
+    if let Some(register) = parse_register(token, isa)? {
+        return Ok(Combo::Register(register));
+    }
+    match token.text.parse::<u8>() {
+        Ok(n) if n <= 3 => Ok(Combo::Literal(n)),
+        // the disassembler prints the reserved operand as 7
+        Ok(7) => match isa.reserved_combo() {
+            ReservedCombo::Invalid => Ok(Combo::Reserved),
+            ReservedCombo::RegisterD => Ok(Combo::Register(Register::D)),
+        },
+        _ => Err(token.error(format!(
+            "Invalid combo operand '{}', expected 0-3, 7, A, B or C",
+            token.text
+        ))),
+    }
+}
+
+fn parse_literal(token: &Token) -> Result<Literal, AssembleError> {
+    token
+        .text
+        .parse()
+        .ok()
+        .and_then(|n| Literal::parse(n).ok())
+        .ok_or_else(|| {
+            token.error(format!(
+                "Invalid literal operand '{}', expected 0-7",
+                token.text
+            ))
+        })
+}
+
+fn parse_jump_target(
+    token: &Token,
+    labels: &HashMap<String, usize>,
+) -> Result<Literal, AssembleError> {
+    if !is_identifier(&token.text) {
+        return parse_literal(token);
+    }
+    let pc = *labels
+        .get(&token.text)
+        .ok_or_else(|| token.error(format!("Undefined label '{}'", token.text)))?;
+
+    u8::try_from(pc)
+        .ok()
+        .and_then(|pc| Literal::parse(pc).ok())
+        .ok_or_else(|| {
+            token.error(format!(
+                "Label '{}' at pc {} is out of range for jnz",
+                token.text, pc
+            ))
+        })
+}
+
+fn parse_register(token: &Token, isa: &Isa) -> Result<Option<Register>, AssembleError> {
+    match token.text.as_str() {
+        "A" | "a" => Ok(Some(Register::A)),
+        "B" | "b" => Ok(Some(Register::B)),
+        "C" | "c" => Ok(Some(Register::C)),
+        "D" | "d" if isa.reserved_combo() == ReservedCombo::RegisterD => Ok(Some(Register::D)),
+        "D" | "d" => {
+            Err(token.error("Register D is not available in this instruction set".to_string()))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::parse::{format_input, parse_str};
+
+    use super::*;
+
+    #[test]
+    fn assembles_sample() -> Result<()> {
+        let source = "
+            .register A 729
+            loop:
+                adv 1     ; a = a >> 1
+                out(A)
+                jnz loop
+        ";
+
+        let (program, registers) = assemble(source, &Isa::standard())?;
+
+        assert_eq!(vec![0, 1, 5, 4, 3, 0], program.encode());
+        assert_eq!(729, registers.a);
+        assert_eq!(
+            "Register A: 729\nRegister B: 0\nRegister C: 0\n\nProgram: 0,1,5,4,3,0\n",
+            format_input(&program, &registers)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_disassembly() -> Result<()> {
+        let input = "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 2,4,1,2,7,5,4,0,1,3,5,5,0,3,3,0,5,7\n";
+        let (program, _) = parse_str(input)?;
+        let source: Vec<String> = program.iter_instructions().map(|i| i.to_string()).collect();
+
+        let (assembled, registers) = assemble(&source.join("\n"), &Isa::standard())?;
+
+        assert_eq!(input, format_input(&assembled, &registers));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_forward_labels() -> Result<()> {
+        let source = "jnz end\nbxl 1\nend:out b";
+
+        let (program, _) = assemble(source, &Isa::standard())?;
+
+        assert_eq!(vec![3, 4, 1, 1, 5, 5], program.encode());
+
+        Ok(())
+    }
+
+    #[test]
+    fn assembles_extensions() -> Result<()> {
+        let source = ".register D 2\ndst A\nmul d\nhlt";
+
+        let (program, registers) = assemble(source, &Isa::experimental())?;
+
+        assert_eq!(vec![10, 4, 8, 7, 9, 0], program.encode());
+        assert_eq!(2, registers.d);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let error = |source| {
+            assemble(source, &Isa::standard())
+                .err()
+                .expect("Source should not assemble")
+                .to_string()
+        };
+
+        assert_eq!("2:3: Unknown mnemonic 'mul'", error("adv 1\n  mul 3"));
+        assert_eq!(
+            "1:5: Invalid combo operand '4', expected 0-3, 7, A, B or C",
+            error("adv 4")
+        );
+        assert_eq!("1:1: Missing operand for out", error("out"));
+        assert_eq!("1:5: Undefined label 'nowhere'", error("jnz nowhere"));
+        assert_eq!("2:1: Duplicate label 'x'", error("x: bxc\nx: bxc"));
+        assert_eq!("1:7: Unexpected '2'", error("bxl 1 2"));
+        assert_eq!("1:4: Unclosed '('", error("out(A"));
+        // columns count characters, not bytes
+        assert_eq!("1:11: Unexpected '2'", error("jnz größe 2"));
+        assert_eq!("1:11: Unknown register 'E'", error(".register E 1"));
+        assert_eq!(
+            "1:11: Register D is not available in this instruction set",
+            error(".register D 1")
+        );
+    }
+}
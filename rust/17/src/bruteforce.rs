@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+
+use crate::{compiled::CompiledProgram, program::Program};
+
+/// Condition the output of a run has to fulfill
+pub enum Goal {
+    Equals(Vec<u8>),
+    Prefix(Vec<u8>),
+    Suffix(Vec<u8>),
+    Length(usize),
+}
+
+impl Goal {
+    /// parses `1,2,3`, `prefix:1,2`, `suffix:1,2` or `length:16`
+    pub fn parse(s: &str) -> Result<Self> {
+        let numbers = |s: &str| {
+            s.split(',')
+                .map(|n| n.trim().parse::<u8>())
+                .collect::<Result<Vec<u8>, _>>()
+        };
+        let goal = match s.split_once(':') {
+            None => Goal::Equals(numbers(s)?),
+            Some(("prefix", rest)) => Goal::Prefix(numbers(rest)?),
+            Some(("suffix", rest)) => Goal::Suffix(numbers(rest)?),
+            Some(("length", rest)) => Goal::Length(rest.parse()?),
+            Some((kind, _)) => return Err(anyhow!("Unknown goal {}", kind)),
+        };
+        Ok(goal)
+    }
+
+    pub fn matches(&self, out: &[u8]) -> bool {
+        match self {
+            Goal::Equals(target) => out == target,
+            Goal::Prefix(prefix) => out.starts_with(prefix),
+            Goal::Suffix(suffix) => out.ends_with(suffix),
+            Goal::Length(length) => out.len() == *length,
+        }
+    }
+}
+
+pub struct SearchOptions {
+    pub range: Range<u64>,
+    /// values checked in parallel between two checkpoints
+    pub batch_size: u64,
+    /// file storing the next unchecked value, used to resume an interrupted search
+    pub checkpoint: Option<PathBuf>,
+    pub progress: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            range: 0..1 << 24,
+            batch_size: 1 << 20,
+            checkpoint: None,
+            progress: true,
+        }
+    }
+}
+
+/// Finds the smallest register A in the range whose output fulfills the
+/// predicate by running the program for every value. Runs that fail with an
+/// execution error do not match.
+pub fn search(
+    program: &Program,
+    options: &SearchOptions,
+    predicate: impl Fn(&[u8]) -> bool + Sync,
+) -> Result<Option<u64>> {
+    let compiled = CompiledProgram::new(program);
+    let range = &options.range;
+    let mut start = match &options.checkpoint {
+        Some(path) => load_checkpoint(path, range)?.unwrap_or(range.start),
+        None => range.start,
+    };
+
+    let progress = if options.progress {
+        ProgressBar::new(range.end - range.start)
+    } else {
+        ProgressBar::hidden()
+    };
+    progress.set_style(ProgressStyle::with_template(
+        "{bar:40} {pos}/{len} ({per_sec}, eta {eta})",
+    )?);
+    progress.set_position(start - range.start);
+
+    while start < range.end {
+        let end = range
+            .end
+            .min(start.saturating_add(options.batch_size.max(1)));
+        // the first hit in the batch cancels the checks of all larger values
+        let found = (start..end).into_par_iter().find_first(|a| {
+            compiled
+                .run_with_register_a(*a)
+                .is_ok_and(|out| predicate(&out))
+        });
+        if let Some(a) = found {
+            progress.finish_and_clear();
+            remove_checkpoint(options)?;
+            return Ok(Some(a));
+        }
+
+        start = end;
+        progress.set_position(start - range.start);
+        if let Some(path) = &options.checkpoint {
+            save_checkpoint(path, start)?;
+        }
+    }
+
+    progress.finish_and_clear();
+    remove_checkpoint(options)?;
+    Ok(None)
+}
+
+fn load_checkpoint(path: &Path, range: &Range<u64>) -> Result<Option<u64>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let next: u64 = fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .context(format!("Invalid checkpoint file {}", path.display()))?;
+    if !(range.start..=range.end).contains(&next) {
+        return Err(anyhow!(
+            "Checkpoint {} is outside of the range {}..{}",
+            next,
+            range.start,
+            range.end
+        ));
+    }
+    Ok(Some(next))
+}
+
+/// writes to a temporary file first, so an interrupted write keeps the old checkpoint
+fn save_checkpoint(path: &Path, next: u64) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, format!("{}\n", next))?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+fn remove_checkpoint(options: &SearchOptions) -> Result<()> {
+    match &options.checkpoint {
+        Some(path) if path.exists() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{interpreter::run_with_register_a, parse::program, solver};
+
+    use super::*;
+
+    fn options(range: Range<u64>) -> SearchOptions {
+        SearchOptions {
+            range,
+            batch_size: 1000,
+            checkpoint: None,
+            progress: false,
+        }
+    }
+
+    #[test]
+    fn agrees_with_solver() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+
+        for a in [5, 100, 1234, 4321, 98765] {
+            let target = run_with_register_a(&program, a)?;
+            let goal = Goal::Equals(target.clone());
+
+            let found = search(&program, &options(0..1 << 20), |out| goal.matches(out))?;
+
+            assert_eq!(Some(solver::solve(&program, &target)?), found);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_goals() -> Result<()> {
+        assert!(Goal::parse("1,2,3")?.matches(&[1, 2, 3]));
+        assert!(Goal::parse("prefix:1,2")?.matches(&[1, 2, 3]));
+        assert!(!Goal::parse("suffix:1,2")?.matches(&[1, 2, 3]));
+        assert!(Goal::parse("length:3")?.matches(&[1, 2, 3]));
+        assert!(Goal::parse("quine").is_err());
+        assert!(Goal::parse("between:1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resumes_from_checkpoint() -> Result<()> {
+        // outputs a / 8 % 8 until a is 0
+        let program = program("0,3,5,4,3,0")?;
+        let name = format!("aoc-17-checkpoint-{}", std::process::id());
+        let checkpoint = env::temp_dir().join(name);
+        let options = SearchOptions {
+            checkpoint: Some(checkpoint.clone()),
+            ..options(0..100_000)
+        };
+
+        // 8 and 9 also output [1, 0], but the checkpoint says they were checked already
+        save_checkpoint(&checkpoint, 10)?;
+        let found = search(&program, &options, |out| out == [1, 0])?;
+
+        assert_eq!(Some(10), found);
+        assert!(!checkpoint.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_no_hit_in_range() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        assert_eq!(
+            None,
+            search(&program, &options(0..5000), |out| out.len() > 10)?
+        );
+
+        Ok(())
+    }
+}
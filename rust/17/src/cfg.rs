@@ -0,0 +1,246 @@
+use std::fmt::Display;
+
+use crate::{instructions::Instruction, instructions::Literal, program::Program};
+
+/// Basic blocks of a program, split at jnz instructions and their targets
+pub struct ControlFlowGraph<'a> {
+    pub blocks: Vec<BasicBlock<'a>>,
+}
+
+pub struct BasicBlock<'a> {
+    pub start_pc: usize,
+    /// instructions with their pc, a jnz can only be the last one
+    pub instructions: Vec<(usize, &'a Instruction)>,
+    pub edges: Vec<Edge>,
+}
+
+pub struct Edge {
+    pub target: Target,
+    pub condition: Condition,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    Block(usize),
+    Halt,
+    /// jump to an odd pc, which does not address an instruction
+    Invalid(usize),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Condition {
+    Always,
+    NonZero,
+    Zero,
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Always => Ok(()),
+            Condition::NonZero => write!(f, "a != 0"),
+            Condition::Zero => write!(f, "a == 0"),
+        }
+    }
+}
+
+impl<'a> ControlFlowGraph<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        let instructions: Vec<(usize, &Instruction)> = program
+            .iter_instructions()
+            .enumerate()
+            .map(|(index, instruction)| (index * 2, instruction))
+            .collect();
+
+        let mut leaders = vec![false; instructions.len()];
+        if let Some(first) = leaders.first_mut() {
+            *first = true;
+        }
+        for (pc, instruction) in &instructions {
+            if let Instruction::Jnz(Literal(target)) = instruction {
+                let target = usize::from(*target);
+                if target.is_multiple_of(2) && program.valid_pc(target) {
+                    leaders[target / 2] = true;
+                }
+                if let Some(next) = leaders.get_mut(pc / 2 + 1) {
+                    *next = true;
+                }
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (index, instruction) in instructions.into_iter().enumerate() {
+            if leaders[index] {
+                blocks.push(BasicBlock {
+                    start_pc: instruction.0,
+                    instructions: Vec::new(),
+                    edges: Vec::new(),
+                });
+            }
+            blocks
+                .last_mut()
+                .expect("The first instruction starts a block")
+                .instructions
+                .push(instruction);
+        }
+
+        let block_at = |pc: usize| {
+            if !pc.is_multiple_of(2) {
+                Target::Invalid(pc)
+            } else {
+                blocks
+                    .iter()
+                    .position(|b| b.start_pc == pc)
+                    .map_or(Target::Halt, Target::Block)
+            }
+        };
+        let edges: Vec<Vec<Edge>> = blocks
+            .iter()
+            .map(|block| {
+                let (last_pc, last) = block.instructions.last().expect("Blocks are not empty");
+                let next = block_at(last_pc + 2);
+                match last {
+                    Instruction::Jnz(Literal(target)) => vec![
+                        Edge {
+                            target: block_at(usize::from(*target)),
+                            condition: Condition::NonZero,
+                        },
+                        Edge {
+                            target: next,
+                            condition: Condition::Zero,
+                        },
+                    ],
+                    _ => vec![Edge {
+                        target: next,
+                        condition: Condition::Always,
+                    }],
+                }
+            })
+            .collect();
+        for (block, edges) in blocks.iter_mut().zip(edges) {
+            block.edges = edges;
+        }
+
+        ControlFlowGraph { blocks }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph program {\n");
+        dot.push_str("    node [shape=box fontname=\"monospace\"];\n");
+        dot.push_str("    halt [shape=oval];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(pc, instruction)| format!("{}: {}\\l", pc, instruction))
+                .collect();
+            dot.push_str(&format!("    b{} [label=\"{}\"];\n", index, label));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            for edge in &block.edges {
+                let target = match edge.target {
+                    Target::Block(target) => format!("b{}", target),
+                    Target::Halt => "halt".to_string(),
+                    Target::Invalid(pc) => {
+                        dot.push_str(&format!(
+                            "    invalid{} [shape=oval label=\"invalid pc {}\"];\n",
+                            pc, pc
+                        ));
+                        format!("invalid{}", pc)
+                    }
+                };
+                dot.push_str(&format!(
+                    "    b{} -> {} [label=\"{}\"];\n",
+                    index, target, edge.condition
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Display for ControlFlowGraph<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "block {} (pc {}):", index, block.start_pc)?;
+            for (pc, instruction) in &block.instructions {
+                writeln!(f, "{:>6}  {}", pc, instruction)?;
+            }
+            for edge in &block.edges {
+                match edge.target {
+                    Target::Block(target) => write!(f, "  -> block {}", target)?,
+                    Target::Halt => write!(f, "  -> halt")?,
+                    Target::Invalid(pc) => write!(f, "  -> invalid pc {}", pc)?,
+                }
+                if edge.condition != Condition::Always {
+                    write!(f, " if {}", edge.condition)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::parse::program;
+
+    use super::*;
+
+    #[test]
+    fn single_loop_is_one_block() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+
+        let cfg = ControlFlowGraph::new(&program);
+
+        assert_eq!(1, cfg.blocks.len());
+        assert_eq!(8, cfg.blocks[0].instructions.len());
+        assert!(matches!(cfg.blocks[0].edges[0].target, Target::Block(0)));
+        assert!(matches!(cfg.blocks[0].edges[1].target, Target::Halt));
+
+        Ok(())
+    }
+
+    #[test]
+    fn splits_at_jump_targets() -> Result<()> {
+        // bxl(1), out(B), jnz(2), jnz(7)
+        let program = program("1,1,5,5,3,2,3,7")?;
+
+        let cfg = ControlFlowGraph::new(&program);
+
+        let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start_pc).collect();
+        assert_eq!(vec![0, 2, 6], starts);
+        assert!(matches!(cfg.blocks[0].edges[0].target, Target::Block(1)));
+        assert!(matches!(cfg.blocks[1].edges[0].target, Target::Block(1)));
+        assert!(matches!(cfg.blocks[1].edges[1].target, Target::Block(2)));
+        assert!(matches!(cfg.blocks[2].edges[0].target, Target::Invalid(7)));
+        assert!(matches!(cfg.blocks[2].edges[1].target, Target::Halt));
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_dot() -> Result<()> {
+        let program = program("0,1,5,4,3,0")?;
+
+        let dot = ControlFlowGraph::new(&program).to_dot();
+
+        assert_eq!(
+            "digraph program {
+    node [shape=box fontname=\"monospace\"];
+    halt [shape=oval];
+    b0 [label=\"0: adv(1)\\l2: out(A)\\l4: jnz(0)\\l\"];
+    b0 -> b0 [label=\"a != 0\"];
+    b0 -> halt [label=\"a == 0\"];
+}
+",
+            dot
+        );
+
+        Ok(())
+    }
+}
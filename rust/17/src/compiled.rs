@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+
+use crate::{
+    instructions::{Combo, Instruction, Literal, Operand, Register},
+    interpreter::{ExecutionError, Limits},
+    isa::Flow,
+    program::Program,
+    state::{RegisterState, State},
+};
+
+/// Executes one instruction and returns the index of the next one
+type Op = Box<dyn Fn(&mut State) -> Result<usize, ExecutionError> + Send + Sync>;
+
+/// Program translated into closures with resolved operands and jump targets,
+/// so running it does not dispatch on `Instruction` and `Combo` at every step.
+/// Behaves exactly like running the program with the interpreter.
+pub struct CompiledProgram {
+    ops: Vec<Op>,
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    Literal(u64),
+    A,
+    B,
+    C,
+    D,
+    Reserved,
+}
+
+impl Source {
+    fn new(combo: &Combo) -> Self {
+        match combo {
+            Combo::Literal(n) => Source::Literal(u64::from(*n)),
+            Combo::Register(Register::A) => Source::A,
+            Combo::Register(Register::B) => Source::B,
+            Combo::Register(Register::C) => Source::C,
+            Combo::Register(Register::D) => Source::D,
+            Combo::Reserved => Source::Reserved,
+        }
+    }
+
+    #[inline(always)]
+    fn read(self, registers: &RegisterState, pc: usize) -> Result<u64, ExecutionError> {
+        match self {
+            Source::Literal(n) => Ok(n),
+            Source::A => Ok(registers.a),
+            Source::B => Ok(registers.b),
+            Source::C => Ok(registers.c),
+            Source::D => Ok(registers.d),
+            Source::Reserved => Err(ExecutionError::InvalidComboOperand { pc }),
+        }
+    }
+}
+
+impl CompiledProgram {
+    pub fn new(program: &Program) -> Self {
+        let length = program.iter_instructions().count();
+        let ops = program
+            .iter_instructions()
+            .enumerate()
+            .map(|(index, instruction)| compile(instruction, index, length))
+            .collect();
+
+        CompiledProgram { ops }
+    }
+
+    pub fn run_with_register_a(&self, a: u64) -> Result<Vec<u8>, ExecutionError> {
+        let registers = RegisterState {
+            a,
+            ..Default::default()
+        };
+
+        self.run(registers, &Limits::default())
+    }
+
+    pub fn run(
+        &self,
+        registers: RegisterState,
+        limits: &Limits,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let mut state = State {
+            registers,
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let max_steps = limits.max_steps.unwrap_or(usize::MAX);
+        let max_output = limits.max_output.unwrap_or(usize::MAX);
+        let mut index = 0;
+        let mut steps = 0;
+
+        while let Some(op) = self.ops.get(index) {
+            if steps >= max_steps {
+                return Err(ExecutionError::StepLimitExceeded(steps));
+            }
+            if limits.detect_cycles
+                && !seen.insert((index, state.registers.clone(), state.input.len()))
+            {
+                return Err(ExecutionError::CycleDetected {
+                    pc: index * 2,
+                    registers: state.registers,
+                });
+            }
+
+            index = op(&mut state)?;
+            steps += 1;
+
+            if state.out.len() > max_output {
+                return Err(ExecutionError::OutputLimitExceeded(max_output));
+            }
+        }
+
+        Ok(state.out)
+    }
+}
+
+fn compile(instruction: &Instruction, index: usize, length: usize) -> Op {
+    let pc = index * 2;
+    let next = index + 1;
+
+    match *instruction {
+        Instruction::Adv(ref operand) => {
+            let source = Source::new(operand);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                registers.a /= power_of_two(source.read(registers, pc)?, pc)?;
+                Ok(next)
+            })
+        }
+        Instruction::Bxl(Literal(n)) => {
+            let n = u64::from(n);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                registers.b ^= n;
+                Ok(next)
+            })
+        }
+        Instruction::Bst(ref operand) => {
+            let source = Source::new(operand);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                registers.b = source.read(registers, pc)? % 8;
+                Ok(next)
+            })
+        }
+        Instruction::Jnz(Literal(target)) => {
+            let jump = jump_target(usize::from(target), length);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                if registers.a != 0 {
+                    jump.clone()
+                } else {
+                    Ok(next)
+                }
+            })
+        }
+        Instruction::Bxc(_) => Box::new(move |state| {
+            state.registers.b ^= state.registers.c;
+            Ok(next)
+        }),
+        Instruction::Out(ref operand) => {
+            let source = Source::new(operand);
+            Box::new(move |state| {
+                let value = source.read(&state.registers, pc)? % 8;
+                state.out.push(value as u8);
+                Ok(next)
+            })
+        }
+        Instruction::Bdv(ref operand) => {
+            let source = Source::new(operand);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                registers.b = registers.a / power_of_two(source.read(registers, pc)?, pc)?;
+                Ok(next)
+            })
+        }
+        Instruction::Cdv(ref operand) => {
+            let source = Source::new(operand);
+            Box::new(move |state| {
+                let registers = &mut state.registers;
+                registers.c = registers.a / power_of_two(source.read(registers, pc)?, pc)?;
+                Ok(next)
+            })
+        }
+        Instruction::Extended(extension, ref operand) => {
+            let source = match operand {
+                Operand::Literal(Literal(n)) => Source::Literal(u64::from(*n)),
+                Operand::Combo(combo) => Source::new(combo),
+            };
+            Box::new(move |state| {
+                let value = source.read(&state.registers, pc)?;
+                state.pc = pc;
+                match (extension.execute)(state, value)? {
+                    Flow::Next => Ok(next),
+                    Flow::Jump(target) => jump_target(target, length),
+                    Flow::Halt => Ok(length),
+                    Flow::Wait => Err(ExecutionError::WaitingForInput { pc }),
+                }
+            })
+        }
+    }
+}
+
+/// index of the instruction at the jump target. Like in the interpreter, a
+/// target behind the program halts and an odd target fails.
+fn jump_target(target: usize, length: usize) -> Result<usize, ExecutionError> {
+    if target / 2 >= length {
+        Ok(length)
+    } else if !target.is_multiple_of(2) {
+        Err(ExecutionError::OddPc(target))
+    } else {
+        Ok(target / 2)
+    }
+}
+
+#[inline(always)]
+fn power_of_two(exponent: u64, pc: usize) -> Result<u64, ExecutionError> {
+    if exponent < 64 {
+        Ok(1 << exponent)
+    } else {
+        Err(ExecutionError::Overflow { pc, exponent })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{
+        interpreter::run_with_register_a,
+        isa::Isa,
+        parse::{parse_str_with_isa, program},
+    };
+
+    use super::*;
+
+    fn assert_equivalent(numbers: &str, inputs: impl Iterator<Item = u64>) -> Result<()> {
+        let program = program(numbers)?;
+        let compiled = CompiledProgram::new(&program);
+
+        for a in inputs {
+            let interpreted = run_with_register_a(&program, a)
+                .map_err(|e| e.downcast::<ExecutionError>().expect("Execution error"));
+
+            assert_eq!(interpreted, compiled.run_with_register_a(a), "a = {}", a);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_interpreter_on_input_like_program() -> Result<()> {
+        assert_equivalent("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0", 0..5000)
+    }
+
+    #[test]
+    fn matches_interpreter_on_errors() -> Result<()> {
+        // overflow for a >= 64
+        assert_equivalent("0,4,5,4", 0..100)?;
+        // reserved operand, skipped if a != 0
+        assert_equivalent("3,4,5,7,5,4", 0..3)?;
+        // odd jump target inside the program, jump target behind the program
+        assert_equivalent("3,1,5,4", 0..3)?;
+        assert_equivalent("5,4,3,7", 0..3)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_interpreter_on_extensions() -> Result<()> {
+        // dst(A), out(D), adv(1), jnz(2), mul(D), out(A), hlt(), out(D)
+        let input = "Register A: 0\nRegister B: 0\nRegister C: 0\n\n\
+            Program: 10,4,5,7,0,1,3,2,8,7,5,4,9,0,5,7";
+        let (program, _) = parse_str_with_isa(input, &Isa::experimental())?;
+        let compiled = CompiledProgram::new(&program);
+
+        for a in 0..100 {
+            assert_eq!(
+                run_with_register_a(&program, a)?,
+                compiled.run_with_register_a(a)?
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn enforces_limits() -> Result<()> {
+        let compiled = CompiledProgram::new(&program("5,4,3,0")?);
+        let registers = RegisterState {
+            a: 1,
+            ..Default::default()
+        };
+        let limits = Limits {
+            max_steps: None,
+            max_output: None,
+            detect_cycles: true,
+        };
+
+        assert_eq!(
+            Err(ExecutionError::CycleDetected {
+                pc: 0,
+                registers: registers.clone()
+            }),
+            compiled.run(registers, &limits)
+        );
+        assert_eq!(
+            Err(ExecutionError::StepLimitExceeded(10_000_000)),
+            compiled.run_with_register_a(1)
+        );
+
+        Ok(())
+    }
+}
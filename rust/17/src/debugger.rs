@@ -0,0 +1,414 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    interpreter::{step, ExecutionError, Limits, Status},
+    program::Program,
+    state::State,
+    watch::Watch,
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]        execute the next n instructions (default 1)
+  c, continue        run until a breakpoint is hit, the program halts or the step limit is reached
+  b, break <pc>      set a breakpoint before the instruction at pc
+  d, delete <pc>     remove the breakpoint at pc
+  w, watch <expr>    print expr after every step, e.g. `watch a % 8`
+  unwatch <n>        remove the nth watch expression
+  i, input <values>  append comma separated values to the input, e.g. `input 1,2`
+  r, registers       print pc, registers and the output so far
+  l, list            disassemble the program
+  trace on|off       toggle printing every executed instruction
+  h, help            show this help
+  q, quit            exit the debugger";
+
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break(usize),
+    Delete(usize),
+    Watch(Watch),
+    Unwatch(usize),
+    Input(Vec<u8>),
+    Registers,
+    List,
+    Trace(bool),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+
+        let command = match name {
+            "s" | "step" if argument.is_empty() => Command::Step(1),
+            "s" | "step" => Command::Step(argument.parse()?),
+            "c" | "continue" => Command::Continue,
+            "b" | "break" => Command::Break(argument.parse()?),
+            "d" | "delete" => Command::Delete(argument.parse()?),
+            "w" | "watch" => Command::Watch(Watch::parse(argument)?),
+            "unwatch" => Command::Unwatch(argument.parse()?),
+            "i" | "input" => Command::Input(
+                argument
+                    .split(',')
+                    .map(|n| match n.trim().parse() {
+                        Ok(n) if n < 8 => Ok(n),
+                        _ => Err(anyhow!("Input values must be 3-bit numbers, got {}", n)),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            "r" | "registers" => Command::Registers,
+            "l" | "list" => Command::List,
+            "trace" => match argument {
+                "on" => Command::Trace(true),
+                "off" => Command::Trace(false),
+                _ => bail!("Expected `trace on` or `trace off`"),
+            },
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => bail!(
+                "Unknown command {}, type `help` for a list of commands",
+                name
+            ),
+        };
+
+        Ok(command)
+    }
+}
+
+pub struct Debugger<'a> {
+    program: &'a Program,
+    state: State,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Watch>,
+    trace: bool,
+    /// bounds a single `continue`, so programs that never halt don't hang the debugger
+    limits: Limits,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program, state: State, limits: Limits) -> Self {
+        Debugger {
+            program,
+            state,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            trace: true,
+            limits,
+        }
+    }
+
+    /// read commands line by line until `quit` or the end of the input
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write) -> Result<()> {
+        write!(output, "(dbg) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                match Command::parse(&line).and_then(|command| self.execute(command, output)) {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(()),
+                    Err(e) => writeln!(output, "Error: {}", e)?,
+                }
+            }
+            write!(output, "(dbg) ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)?;
+        Ok(())
+    }
+
+    /// returns false if the debugger should exit
+    pub fn execute(&mut self, command: Command, output: &mut impl Write) -> Result<bool> {
+        match command {
+            Command::Step(n) => {
+                for _ in 0..n {
+                    if !self.step(output)? {
+                        break;
+                    }
+                }
+            }
+            Command::Continue => {
+                let out_length = self.state.out.len();
+                let mut steps = 0;
+                loop {
+                    if self.limits.max_steps.is_some_and(|max| steps >= max) {
+                        writeln!(output, "{}", ExecutionError::StepLimitExceeded(steps))?;
+                        break;
+                    }
+                    if let Some(max) = self.limits.max_output {
+                        if self.state.out.len() - out_length > max {
+                            writeln!(output, "{}", ExecutionError::OutputLimitExceeded(max))?;
+                            break;
+                        }
+                    }
+                    if !self.step(output)? {
+                        break;
+                    }
+                    steps += 1;
+                    if self.breakpoints.contains(&self.state.pc) {
+                        writeln!(output, "Breakpoint at pc {}", self.state.pc)?;
+                        break;
+                    }
+                }
+            }
+            Command::Break(pc) => {
+                if !self.program.valid_pc(pc) || pc % 2 != 0 {
+                    bail!("There is no instruction at pc {}", pc);
+                }
+                self.breakpoints.insert(pc);
+                writeln!(output, "Breakpoint set at pc {}", pc)?;
+            }
+            Command::Delete(pc) => {
+                if !self.breakpoints.remove(&pc) {
+                    bail!("There is no breakpoint at pc {}", pc);
+                }
+                writeln!(output, "Breakpoint at pc {} deleted", pc)?;
+            }
+            Command::Watch(watch) => {
+                writeln!(output, "Watch {}: {}", self.watches.len(), watch)?;
+                self.watches.push(watch);
+            }
+            Command::Unwatch(n) => {
+                if n >= self.watches.len() {
+                    bail!("There is no watch {}", n);
+                }
+                let watch = self.watches.remove(n);
+                writeln!(output, "Removed watch {}", watch)?;
+            }
+            Command::Input(values) => {
+                self.state.input.extend(values);
+                writeln!(
+                    output,
+                    "Input: {}",
+                    format_out(self.state.input.make_contiguous())
+                )?;
+            }
+            Command::Registers => {
+                writeln!(output, "pc: {} {}", self.state.pc, self.state.registers)?;
+                writeln!(output, "Out: {}", format_out(&self.state.out))?;
+                if !self.state.input.is_empty() {
+                    writeln!(
+                        output,
+                        "Input: {}",
+                        format_out(self.state.input.make_contiguous())
+                    )?;
+                }
+            }
+            Command::List => {
+                for (index, instruction) in self.program.iter_instructions().enumerate() {
+                    let pc = index * 2;
+                    let marker = if pc == self.state.pc { ">" } else { " " };
+                    let breakpoint = if self.breakpoints.contains(&pc) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    writeln!(output, "{}{}{:>4}  {}", marker, breakpoint, pc, instruction)?;
+                }
+            }
+            Command::Trace(trace) => self.trace = trace,
+            Command::Help => writeln!(output, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// returns false if the program has already halted or waits for input
+    fn step(&mut self, output: &mut impl Write) -> Result<bool> {
+        if !self.program.valid_pc(self.state.pc) {
+            writeln!(
+                output,
+                "Program halted. Out: {}",
+                format_out(&self.state.out)
+            )?;
+            return Ok(false);
+        }
+
+        let pc = self.state.pc;
+        let instruction = self
+            .program
+            .get_instruction(pc)
+            .ok_or(anyhow!("Invalid pc {}", pc))?;
+        let out_length = self.state.out.len();
+        if step(self.program, &mut self.state)? == Status::Blocked {
+            writeln!(
+                output,
+                "Waiting for input at pc {}, provide it with `input`",
+                pc
+            )?;
+            return Ok(false);
+        }
+
+        if self.trace {
+            write!(
+                output,
+                "{:>4}  {:<8} {}",
+                pc,
+                instruction.to_string(),
+                self.state.registers
+            )?;
+            if let Some(n) = self.state.out.get(out_length) {
+                write!(output, " out {}", n)?;
+            }
+            writeln!(output)?;
+        }
+        for watch in &self.watches {
+            match watch.evaluate(&self.state) {
+                Some(value) => writeln!(output, "      {} = {}", watch, value)?,
+                None => writeln!(output, "      {} = <division by zero>", watch)?,
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn format_out(out: &[u8]) -> String {
+    let out: Vec<String> = out.iter().map(|n| n.to_string()).collect();
+    out.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        isa::Isa,
+        parse::{parse_str, parse_str_with_isa},
+    };
+
+    use super::*;
+
+    const SAMPLE: &str = "Register A: 729
+Register B: 0
+Register C: 0
+
+Program: 0,1,5,4,3,0";
+
+    fn run_commands(commands: &str) -> Result<(String, State)> {
+        let (program, state) = parse_str(SAMPLE)?;
+        let mut debugger = Debugger::new(&program, state, Limits::default());
+        let mut output = Vec::new();
+
+        debugger.run(commands.as_bytes(), &mut output)?;
+
+        Ok((String::from_utf8(output)?, debugger.state().clone()))
+    }
+
+    #[test]
+    fn steps_through_instructions() -> Result<()> {
+        let (output, state) = run_commands("step\nstep 2\n")?;
+
+        assert!(output.contains("   0  adv(1)   A: 364 B: 0 C: 0"));
+        assert!(output.contains("   2  out(A)   A: 364 B: 0 C: 0 out 4"));
+        assert!(output.contains("   4  jnz(0)   A: 364 B: 0 C: 0"));
+        assert_eq!(0, state.pc);
+        assert_eq!(vec![4], state.out);
+
+        Ok(())
+    }
+
+    #[test]
+    fn continues_until_breakpoint() -> Result<()> {
+        let (output, state) = run_commands("break 2\ncontinue\ncontinue\n")?;
+
+        assert!(output.contains("Breakpoint at pc 2"));
+        assert_eq!(2, state.pc);
+        assert_eq!(vec![4], state.out);
+
+        Ok(())
+    }
+
+    #[test]
+    fn continues_until_halt() -> Result<()> {
+        let (output, state) = run_commands("trace off\ncontinue\nstep\nquit\nstep\n")?;
+
+        assert_eq!(vec![4, 6, 3, 5, 6, 3, 5, 2, 1, 0], state.out);
+        assert!(output.contains("Program halted. Out: 4,6,3,5,6,3,5,2,1,0"));
+        assert!(!output.contains("adv"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn prints_watches_after_each_step() -> Result<()> {
+        let (output, _) = run_commands("watch a % 8\nstep\nunwatch 0\nstep\n")?;
+
+        assert_eq!(1, output.matches("a % 8 = 4").count());
+
+        let (output, _) = run_commands("trace off\nwatch a\nstep\n")?;
+
+        assert!(output.contains("a = 364"));
+        assert!(!output.contains("adv"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn stops_programs_that_never_halt() -> Result<()> {
+        // jnz(0) with a = 1
+        let (program, state) =
+            parse_str("Register A: 1\nRegister B: 0\nRegister C: 0\n\nProgram: 3,0")?;
+        let limits = Limits {
+            max_steps: Some(100),
+            ..Default::default()
+        };
+        let mut debugger = Debugger::new(&program, state, limits);
+        let mut output = Vec::new();
+
+        debugger.run("trace off\ncontinue\n".as_bytes(), &mut output)?;
+
+        let output = String::from_utf8(output)?;
+        assert!(output.contains("Program did not halt within 100 steps"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn waits_for_input() -> Result<()> {
+        // inp(), out(B), jnz(0) with a = 1
+        let input = "Register A: 1\nRegister B: 0\nRegister C: 0\n\nProgram: 11,0,5,5,3,0";
+        let (program, state) = parse_str_with_isa(input, &Isa::experimental())?;
+        let mut debugger = Debugger::new(&program, state, Limits::default());
+        let mut output = Vec::new();
+
+        debugger.run(
+            "continue\ninput 3,9\ninput 3,4\ncontinue\n".as_bytes(),
+            &mut output,
+        )?;
+
+        let output = String::from_utf8(output)?;
+        assert_eq!(2, output.matches("Waiting for input at pc 0").count());
+        assert!(output.contains("Input values must be 3-bit numbers, got 9"));
+        assert_eq!(vec![3, 4], debugger.state().out);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_invalid_commands() -> Result<()> {
+        let (output, state) = run_commands("jump\nbreak 1\nbreak 6\ndelete 2\nstep\n")?;
+
+        assert!(output.contains("Unknown command jump"));
+        assert!(output.contains("There is no instruction at pc 1"));
+        assert!(output.contains("There is no instruction at pc 6"));
+        assert!(output.contains("There is no breakpoint at pc 2"));
+        assert_eq!(2, state.pc);
+
+        Ok(())
+    }
+}
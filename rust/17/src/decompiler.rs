@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+
+use crate::{
+    cfg::{BasicBlock, Condition, ControlFlowGraph, Target},
+    instructions::{Combo, Instruction, Literal},
+    program::Program,
+};
+
+const INDENT: &str = "    ";
+
+/// Renders the program as pseudo-code. Back edges that form properly nested
+/// loops become `do { ... } while (a != 0)`, every other jump a labeled goto.
+pub fn decompile(program: &Program) -> String {
+    let cfg = ControlFlowGraph::new(program);
+    let loops = find_loops(&cfg);
+    let structured: HashSet<usize> = loops.iter().map(|(_, end)| *end).collect();
+    let labels: HashSet<usize> = cfg
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !structured.contains(index))
+        .flat_map(|(_, block)| block.edges.iter())
+        .filter(|edge| edge.condition == Condition::NonZero)
+        .filter_map(|edge| match edge.target {
+            Target::Block(target) => Some(target),
+            _ => None,
+        })
+        .collect();
+
+    let decompiler = Decompiler {
+        cfg: &cfg,
+        loops,
+        labels,
+    };
+    let mut lines = Vec::new();
+    decompiler.render(0, cfg.blocks.len(), 0, &mut lines);
+
+    lines.join("\n") + "\n"
+}
+
+struct Decompiler<'a> {
+    cfg: &'a ControlFlowGraph<'a>,
+    /// (header, last block) of every structured loop
+    loops: Vec<(usize, usize)>,
+    labels: HashSet<usize>,
+}
+
+impl Decompiler<'_> {
+    /// renders the blocks in start..end
+    fn render(&self, start: usize, end: usize, depth: usize, lines: &mut Vec<String>) {
+        let mut index = start;
+        while index < end {
+            if self.labels.contains(&index) {
+                lines.push(self.label(index, depth));
+            }
+
+            // the outermost loop starting here that fits into the range
+            let enclosing_loop = self
+                .loops
+                .iter()
+                .filter(|(header, last)| *header == index && *last < end)
+                .map(|(_, last)| *last)
+                .max();
+
+            match enclosing_loop {
+                Some(last) => {
+                    lines.push(format!("{}do {{", INDENT.repeat(depth)));
+                    self.render_loop_body(index, last, depth + 1, lines);
+                    lines.push(format!("{}}} while (a != 0)", INDENT.repeat(depth)));
+                    index = last + 1;
+                }
+                None => {
+                    self.render_block(&self.cfg.blocks[index], true, depth, lines);
+                    index += 1;
+                }
+            }
+        }
+    }
+
+    fn render_loop_body(&self, header: usize, last: usize, depth: usize, lines: &mut Vec<String>) {
+        // all blocks except the last one, which holds the loop condition
+        self.render(header, last, depth, lines);
+        if header < last && self.labels.contains(&last) {
+            lines.push(self.label(last, depth));
+        }
+        self.render_block(&self.cfg.blocks[last], false, depth, lines);
+    }
+
+    fn label(&self, block: usize, depth: usize) -> String {
+        format!(
+            "{}L{}:",
+            INDENT.repeat(depth),
+            self.cfg.blocks[block].start_pc
+        )
+    }
+
+    fn render_block(
+        &self,
+        block: &BasicBlock,
+        with_jump: bool,
+        depth: usize,
+        lines: &mut Vec<String>,
+    ) {
+        let indent = INDENT.repeat(depth);
+        for (_, instruction) in &block.instructions {
+            match instruction {
+                Instruction::Jnz(_) => {
+                    if with_jump {
+                        let target = match block.edges[0].target {
+                            Target::Block(target) => {
+                                format!("L{}", self.cfg.blocks[target].start_pc)
+                            }
+                            Target::Halt => "halt".to_string(),
+                            Target::Invalid(pc) => format!("invalid pc {}", pc),
+                        };
+                        lines.push(format!("{}if (a != 0) goto {}", indent, target));
+                    }
+                }
+                _ => lines.push(format!("{}{}", indent, statement(instruction))),
+            }
+        }
+    }
+}
+
+/// loops as (header, last block) pairs, only keeping properly nested ones
+/// that are not entered from outside except through their header
+fn find_loops(cfg: &ControlFlowGraph) -> Vec<(usize, usize)> {
+    let mut loops: Vec<(usize, usize)> = Vec::new();
+
+    for (last, block) in cfg.blocks.iter().enumerate() {
+        let Some(Target::Block(header)) = block
+            .edges
+            .iter()
+            .find(|edge| edge.condition == Condition::NonZero)
+            .map(|edge| edge.target)
+        else {
+            continue;
+        };
+        if header > last {
+            continue;
+        }
+
+        let entered_from_outside = cfg.blocks.iter().enumerate().any(|(from, block)| {
+            !(header..=last).contains(&from)
+                && block
+                    .edges
+                    .iter()
+                    .any(|edge| matches!(edge.target, Target::Block(t) if header < t && t <= last))
+        });
+        let crosses_other_loop = loops.iter().any(|(other_header, other_last)| {
+            (*other_header < header && header <= *other_last && *other_last < last)
+                || (header < *other_header && *other_header <= last && last < *other_last)
+        });
+        if !entered_from_outside && !crosses_other_loop {
+            loops.push((header, last));
+        }
+    }
+
+    loops
+}
+
+fn statement(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Adv(combo) => format!("a >>= {}", value(combo)),
+        Instruction::Bxl(Literal(n)) => format!("b ^= {}", n),
+        Instruction::Bst(Combo::Literal(n)) => format!("b = {}", n),
+        Instruction::Bst(combo) => format!("b = {} % 8", value(combo)),
+        Instruction::Jnz(Literal(n)) => format!("if (a != 0) goto pc {}", n),
+        Instruction::Bxc(_) => "b ^= c".to_string(),
+        Instruction::Out(Combo::Literal(n)) => format!("out({})", n),
+        Instruction::Out(combo) => format!("out({} % 8)", value(combo)),
+        Instruction::Bdv(combo) => format!("b = a >> {}", value(combo)),
+        Instruction::Cdv(combo) => format!("c = a >> {}", value(combo)),
+        Instruction::Extended(..) => instruction.to_string(),
+    }
+}
+
+fn value(combo: &Combo) -> String {
+    match combo {
+        Combo::Literal(n) => n.to_string(),
+        Combo::Register(register) => register.to_string().to_lowercase(),
+        Combo::Reserved => "<reserved 7>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::parse::program;
+
+    use super::*;
+
+    #[test]
+    fn decompiles_input_loop() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+
+        assert_eq!(
+            "do {
+    b = a % 8
+    b ^= 1
+    c = a >> b
+    b ^= 5
+    b ^= c
+    a >>= 3
+    out(b % 8)
+} while (a != 0)
+",
+            decompile(&program)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompiles_nested_loops() -> Result<()> {
+        // outer loop jumping back to pc 0, inner loop jumping back to pc 2
+        let program = program("5,4,0,1,3,2,0,1,3,0")?;
+
+        assert_eq!(
+            "do {
+    out(a % 8)
+    do {
+        a >>= 1
+    } while (a != 0)
+    a >>= 1
+} while (a != 0)
+",
+            decompile(&program)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn falls_back_to_goto() -> Result<()> {
+        // forward jump over the output
+        let program = program("0,1,3,6,5,4,5,5")?;
+
+        assert_eq!(
+            "a >>= 1
+if (a != 0) goto L6
+out(a % 8)
+L6:
+out(b % 8)
+",
+            decompile(&program)
+        );
+
+        Ok(())
+    }
+}
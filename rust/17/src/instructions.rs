@@ -2,6 +2,11 @@ use std::fmt::Display;
 
 use anyhow::{anyhow, Error, Result};
 
+use crate::isa::{Extension, OperandKind};
+
+/// mnemonics of the standard opcodes 0-7, in opcode order
+pub const STANDARD_MNEMONICS: [&str; 8] = ["adv", "bxl", "bst", "jnz", "bxc", "out", "bdv", "cdv"];
+
 pub enum Instruction {
     Adv(Combo),
     Bxl(Literal),
@@ -11,6 +16,13 @@ pub enum Instruction {
     Out(Combo),
     Bdv(Combo),
     Cdv(Combo),
+    /// opcode defined by the instruction set, see `Isa`
+    Extended(&'static Extension, Operand),
+}
+
+pub enum Operand {
+    Literal(Literal),
+    Combo(Combo),
 }
 
 impl Instruction {
@@ -27,6 +39,18 @@ impl Instruction {
             _ => Err(anyhow!("Invalid opcode {}", opcode)),
         }
     }
+
+    pub fn combo_mut(&mut self) -> Option<&mut Combo> {
+        match self {
+            Instruction::Adv(combo)
+            | Instruction::Bst(combo)
+            | Instruction::Out(combo)
+            | Instruction::Bdv(combo)
+            | Instruction::Cdv(combo)
+            | Instruction::Extended(_, Operand::Combo(combo)) => Some(combo),
+            _ => None,
+        }
+    }
 }
 
 impl From<&Instruction> for (u8, u8) {
@@ -40,6 +64,12 @@ impl From<&Instruction> for (u8, u8) {
             Instruction::Out(combo) => (5, combo.into()),
             Instruction::Bdv(combo) => (6, combo.into()),
             Instruction::Cdv(combo) => (7, combo.into()),
+            Instruction::Extended(extension, Operand::Literal(literal)) => {
+                (extension.opcode, literal.into())
+            }
+            Instruction::Extended(extension, Operand::Combo(combo)) => {
+                (extension.opcode, combo.into())
+            }
         }
     }
 }
@@ -55,6 +85,15 @@ impl Display for Instruction {
             Instruction::Out(combo) => write!(f, "out({})", combo),
             Instruction::Bdv(combo) => write!(f, "bdv({})", combo),
             Instruction::Cdv(combo) => write!(f, "cdv({})", combo),
+            Instruction::Extended(extension, _) if extension.operand == OperandKind::Ignored => {
+                write!(f, "{}()", extension.mnemonic)
+            }
+            Instruction::Extended(extension, Operand::Literal(Literal(n))) => {
+                write!(f, "{}({})", extension.mnemonic, n)
+            }
+            Instruction::Extended(extension, Operand::Combo(combo)) => {
+                write!(f, "{}({})", extension.mnemonic, combo)
+            }
         }
     }
 }
@@ -80,6 +119,9 @@ impl From<&Literal> for u8 {
 pub enum Combo {
     Literal(u8),
     Register(Register),
+    /// operand 7, which does not appear in valid programs and fails when executed.
+    /// Instruction sets can decode it as register D instead.
+    Reserved,
 }
 
 impl Combo {
@@ -89,6 +131,7 @@ impl Combo {
             4 => Ok(Self::Register(Register::A)),
             5 => Ok(Self::Register(Register::B)),
             6 => Ok(Self::Register(Register::C)),
+            7 => Ok(Self::Reserved),
             _ => Err(anyhow!("Invalid combo operand {}", operand)),
         }
     }
@@ -101,6 +144,7 @@ impl From<&Combo> for u8 {
             Combo::Register(Register::A) => 4,
             Combo::Register(Register::B) => 5,
             Combo::Register(Register::C) => 6,
+            Combo::Register(Register::D) | Combo::Reserved => 7,
         }
     }
 }
@@ -110,6 +154,7 @@ impl Display for Combo {
         match self {
             Combo::Literal(n) => write!(f, "{}", n),
             Combo::Register(register) => write!(f, "{}", register),
+            Combo::Reserved => write!(f, "7"),
         }
     }
 }
@@ -118,6 +163,8 @@ pub enum Register {
     A,
     B,
     C,
+    /// only available in instruction sets mapping combo operand 7 to it
+    D,
 }
 
 impl Display for Register {
@@ -126,6 +173,7 @@ impl Display for Register {
             Register::A => write!(f, "A"),
             Register::B => write!(f, "B"),
             Register::C => write!(f, "C"),
+            Register::D => write!(f, "D"),
         }
     }
 }
@@ -1,68 +1,348 @@
+use std::{collections::HashSet, fmt::Display};
+
 use anyhow::{Context, Result};
 
 use crate::{
-    instructions::{Combo, Instruction, Literal, Register},
+    instructions::{Combo, Instruction, Literal, Operand, Register},
+    isa::Flow,
     program::Program,
-    state::State,
+    state::{RegisterState, State},
 };
 
-pub fn step(program: &Program, state: &mut State) -> Result<()> {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionError {
+    /// a division by 2^n where 2^n does not fit into 64 bits
+    Overflow {
+        pc: usize,
+        exponent: u64,
+    },
+    /// combo operand 7 is reserved and must not be executed
+    InvalidComboOperand {
+        pc: usize,
+    },
+    /// pcs always point to the opcode of an instruction, which is at an even position
+    OddPc(usize),
+    StepLimitExceeded(usize),
+    OutputLimitExceeded(usize),
+    /// the same pc and registers were reached twice, so the program never halts
+    CycleDetected {
+        pc: usize,
+        registers: RegisterState,
+    },
+    /// the program waits for input, but nothing can provide it
+    WaitingForInput {
+        pc: usize,
+    },
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::Overflow { pc, exponent } => {
+                write!(
+                    f,
+                    "Overflow at pc {}: 2^{} does not fit into 64 bits",
+                    pc, exponent
+                )
+            }
+            ExecutionError::InvalidComboOperand { pc } => {
+                write!(f, "Invalid combo operand 7 at pc {}", pc)
+            }
+            ExecutionError::OddPc(pc) => write!(f, "Odd pc {}", pc),
+            ExecutionError::StepLimitExceeded(limit) => {
+                write!(f, "Program did not halt within {} steps", limit)
+            }
+            ExecutionError::OutputLimitExceeded(limit) => {
+                write!(f, "Program output more than {} values", limit)
+            }
+            ExecutionError::CycleDetected { pc, registers } => {
+                write!(f, "Infinite loop detected at pc {} with {}", pc, registers)
+            }
+            ExecutionError::WaitingForInput { pc } => {
+                write!(f, "Program waits for input at pc {}", pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// Bounds for running a program to completion
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_steps: Option<usize>,
+    pub max_output: Option<usize>,
+    /// remembers every (pc, registers) state, which costs memory for long runs
+    pub detect_cycles: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: Some(10_000_000),
+            max_output: None,
+            detect_cycles: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Running,
+    /// waiting for input, stepping again after providing it resumes the program
+    Blocked,
+    Halted,
+}
+
+/// Executes the instruction at the pc. On errors the state is left unchanged.
+pub fn step(program: &Program, state: &mut State) -> Result<Status> {
     let pc = state.pc;
+    if !pc.is_multiple_of(2) {
+        return Err(ExecutionError::OddPc(pc).into());
+    }
     let instruction = program
         .get_instruction(pc)
         .context(format!("Invalid pc {}", pc))?;
 
-    execute_instruction(state, instruction);
+    match execute_instruction(state, instruction)? {
+        Flow::Next => state.pc += 2,
+        Flow::Jump(target) => state.pc = target,
+        Flow::Halt => state.pc = program.end_pc(),
+        Flow::Wait => return Ok(Status::Blocked),
+    }
+
+    if program.valid_pc(state.pc) {
+        Ok(Status::Running)
+    } else {
+        Ok(Status::Halted)
+    }
+}
+
+/// Steps until the program halts or one of the limits is hit. Fails if the
+/// program waits for more input than the state holds.
+pub fn run(program: &Program, state: &mut State, limits: &Limits) -> Result<()> {
+    match run_until_blocked(program, state, limits)? {
+        Status::Blocked => Err(ExecutionError::WaitingForInput { pc: state.pc }.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Steps until the program halts, waits for input or one of the limits is hit
+pub fn run_until_blocked(program: &Program, state: &mut State, limits: &Limits) -> Result<Status> {
+    let mut seen = HashSet::new();
+    let mut steps = 0;
+
+    while program.valid_pc(state.pc) {
+        if limits.max_steps.is_some_and(|max| steps >= max) {
+            return Err(ExecutionError::StepLimitExceeded(steps).into());
+        }
+        // the input only shrinks while running, so its length identifies it
+        if limits.detect_cycles
+            && !seen.insert((state.pc, state.registers.clone(), state.input.len()))
+        {
+            return Err(ExecutionError::CycleDetected {
+                pc: state.pc,
+                registers: state.registers.clone(),
+            }
+            .into());
+        }
+
+        if step(program, state)? == Status::Blocked {
+            return Ok(Status::Blocked);
+        }
+        steps += 1;
+
+        if let Some(max) = limits.max_output {
+            if state.out.len() > max {
+                return Err(ExecutionError::OutputLimitExceeded(max).into());
+            }
+        }
+    }
+
+    Ok(Status::Halted)
+}
+
+pub fn run_with_register_a(program: &Program, a: u64) -> Result<Vec<u8>> {
+    let mut state = State {
+        registers: RegisterState {
+            a,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    run(program, &mut state, &Limits::default())?;
 
-    Ok(())
+    Ok(state.out)
 }
 
-fn execute_instruction(state: &mut State, instruction: &Instruction) {
-    let mut jumped = false;
+fn execute_instruction(
+    state: &mut State,
+    instruction: &Instruction,
+) -> Result<Flow, ExecutionError> {
     match instruction {
         Instruction::Adv(operand) => {
-            state.registers.a /= 2_u64.pow(parse_operand(operand, state).try_into().unwrap());
+            state.registers.a /= power_of_two(operand, state)?;
         }
         Instruction::Bxl(Literal(n)) => {
             state.registers.b ^= u64::from(*n);
         }
         Instruction::Bst(operand) => {
-            state.registers.b = parse_operand(operand, state) % 8;
+            state.registers.b = parse_operand(operand, state)? % 8;
         }
         Instruction::Jnz(Literal(n)) => {
             if state.registers.a != 0 {
-                state.pc = usize::from(*n);
-                jumped = true;
+                return Ok(Flow::Jump(usize::from(*n)));
             }
         }
         Instruction::Bxc(_) => {
             state.registers.b ^= state.registers.c;
         }
         Instruction::Out(operand) => {
+            let value = parse_operand(operand, state)? % 8;
             state
                 .out
-                .push((parse_operand(operand, state) % 8).try_into().unwrap());
+                .push(value.try_into().expect("Values mod 8 fit into u8"));
         }
         Instruction::Bdv(operand) => {
-            state.registers.b =
-                state.registers.a / 2_u64.pow(parse_operand(operand, state).try_into().unwrap());
+            state.registers.b = state.registers.a / power_of_two(operand, state)?;
         }
         Instruction::Cdv(operand) => {
-            state.registers.c =
-                state.registers.a / 2_u64.pow(parse_operand(operand, state).try_into().unwrap());
+            state.registers.c = state.registers.a / power_of_two(operand, state)?;
+        }
+        Instruction::Extended(extension, operand) => {
+            let value = match operand {
+                Operand::Literal(Literal(n)) => u64::from(*n),
+                Operand::Combo(combo) => parse_operand(combo, state)?,
+            };
+            return (extension.execute)(state, value);
         }
     }
 
-    if !jumped {
-        state.pc += 2;
-    }
+    Ok(Flow::Next)
 }
 
-fn parse_operand(operand: &Combo, state: &State) -> u64 {
+fn power_of_two(operand: &Combo, state: &State) -> Result<u64, ExecutionError> {
+    let exponent = parse_operand(operand, state)?;
+    u32::try_from(exponent)
+        .ok()
+        .and_then(|exponent| 2_u64.checked_pow(exponent))
+        .ok_or(ExecutionError::Overflow {
+            pc: state.pc,
+            exponent,
+        })
+}
+
+fn parse_operand(operand: &Combo, state: &State) -> Result<u64, ExecutionError> {
     match operand {
-        Combo::Literal(n) => u64::from(*n),
-        Combo::Register(Register::A) => state.registers.a,
-        Combo::Register(Register::B) => state.registers.b,
-        Combo::Register(Register::C) => state.registers.c,
+        Combo::Literal(n) => Ok(u64::from(*n)),
+        Combo::Register(Register::A) => Ok(state.registers.a),
+        Combo::Register(Register::B) => Ok(state.registers.b),
+        Combo::Register(Register::C) => Ok(state.registers.c),
+        Combo::Register(Register::D) => Ok(state.registers.d),
+        Combo::Reserved => Err(ExecutionError::InvalidComboOperand { pc: state.pc }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{isa::Isa, parse::machine};
+
+    use super::*;
+
+    fn parse(a: u64, numbers: &str) -> Result<(Program, State)> {
+        machine(a, numbers, &Isa::standard())
+    }
+
+    fn execution_error<T: std::fmt::Debug>(result: Result<T>) -> ExecutionError {
+        result
+            .expect_err("Should fail")
+            .downcast()
+            .expect("Should fail with an ExecutionError")
+    }
+
+    #[test]
+    fn reports_overflow_instead_of_panicking() -> Result<()> {
+        // adv(A) with A = 64
+        let (program, mut state) = parse(64, "0,4")?;
+
+        assert_eq!(
+            ExecutionError::Overflow {
+                pc: 0,
+                exponent: 64
+            },
+            execution_error(step(&program, &mut state))
+        );
+        assert_eq!(64, state.registers.a);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_reserved_combo_operand_when_executed() -> Result<()> {
+        // jump over out(7)
+        let (program, mut state) = parse(1, "3,4,5,7,5,4")?;
+
+        run(&program, &mut state, &Limits::default())?;
+        assert_eq!(vec![1], state.out);
+
+        let (program, mut state) = parse(0, "5,7")?;
+        assert_eq!(
+            ExecutionError::InvalidComboOperand { pc: 0 },
+            execution_error(step(&program, &mut state))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_odd_pc() -> Result<()> {
+        let (program, mut state) = parse(1, "3,1,5,4")?;
+
+        step(&program, &mut state)?;
+
+        assert_eq!(
+            ExecutionError::OddPc(1),
+            execution_error(step(&program, &mut state))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn enforces_limits() -> Result<()> {
+        // out(A), jnz(0) never changes a
+        let (program, state) = parse(1, "5,4,3,0")?;
+        let limits = |max_steps, max_output, detect_cycles| Limits {
+            max_steps,
+            max_output,
+            detect_cycles,
+        };
+
+        assert_eq!(
+            ExecutionError::StepLimitExceeded(10),
+            execution_error(run(
+                &program,
+                &mut state.clone(),
+                &limits(Some(10), None, false)
+            ))
+        );
+        assert_eq!(
+            ExecutionError::OutputLimitExceeded(3),
+            execution_error(run(
+                &program,
+                &mut state.clone(),
+                &limits(None, Some(3), false)
+            ))
+        );
+        assert_eq!(
+            ExecutionError::CycleDetected {
+                pc: 0,
+                registers: state.registers.clone()
+            },
+            execution_error(run(&program, &mut state.clone(), &limits(None, None, true)))
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    instructions::{Combo, Instruction, Literal, Operand, Register, STANDARD_MNEMONICS},
+    interpreter::ExecutionError,
+    state::State,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    Literal,
+    Combo,
+    /// encoded like a literal, but not shown by the disassembler
+    Ignored,
+}
+
+/// What happens after an extended instruction was executed
+pub enum Flow {
+    Next,
+    Jump(usize),
+    Halt,
+    /// the instruction cannot execute yet, the pc stays and the machine yields
+    Wait,
+}
+
+/// Definition of an opcode outside of the standard 0-7.
+///
+/// Decoded instructions point to their definition, so the disassembler and the
+/// encoder use the same mnemonic and opcode as the decoder. Jumps and halts of
+/// extensions are not visible to the control-flow graph.
+#[derive(Debug)]
+pub struct Extension {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub operand: OperandKind,
+    /// receives the operand resolved according to its kind, 0 if it is ignored
+    pub execute: fn(&mut State, u64) -> Result<Flow, ExecutionError>,
+}
+
+/// a = a * operand, wrapping around on overflow
+pub static MUL: Extension = Extension {
+    opcode: 8,
+    mnemonic: "mul",
+    operand: OperandKind::Combo,
+    execute: |state, operand| {
+        state.registers.a = state.registers.a.wrapping_mul(operand);
+        Ok(Flow::Next)
+    },
+};
+
+pub static HLT: Extension = Extension {
+    opcode: 9,
+    mnemonic: "hlt",
+    operand: OperandKind::Ignored,
+    execute: |_, _| Ok(Flow::Halt),
+};
+
+/// d = operand
+pub static DST: Extension = Extension {
+    opcode: 10,
+    mnemonic: "dst",
+    operand: OperandKind::Combo,
+    execute: |state, operand| {
+        state.registers.d = operand;
+        Ok(Flow::Next)
+    },
+};
+
+/// b = next input value, waits while the input is empty
+pub static INP: Extension = Extension {
+    opcode: 11,
+    mnemonic: "inp",
+    operand: OperandKind::Ignored,
+    execute: |state, _| match state.input.pop_front() {
+        Some(value) => {
+            state.registers.b = u64::from(value);
+            Ok(Flow::Next)
+        }
+        None => Ok(Flow::Wait),
+    },
+};
+
+/// Meaning of combo operand 7
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReservedCombo {
+    /// decodes, but fails when executed
+    Invalid,
+    RegisterD,
+}
+
+/// Instruction set used to decode and assemble programs
+pub struct Isa {
+    extensions: Vec<&'static Extension>,
+    reserved_combo: ReservedCombo,
+}
+
+impl Isa {
+    /// the eight opcodes of the puzzle
+    pub fn standard() -> Self {
+        Isa {
+            extensions: Vec::new(),
+            reserved_combo: ReservedCombo::Invalid,
+        }
+    }
+
+    /// the standard opcodes plus mul, hlt, dst, inp and register D as combo operand 7
+    pub fn experimental() -> Self {
+        Isa::standard()
+            .with_extension(&MUL)
+            .and_then(|isa| isa.with_extension(&HLT))
+            .and_then(|isa| isa.with_extension(&DST))
+            .and_then(|isa| isa.with_extension(&INP))
+            .expect("Experimental extensions do not overlap")
+            .with_reserved_combo(ReservedCombo::RegisterD)
+    }
+
+    pub fn with_extension(mut self, extension: &'static Extension) -> Result<Self> {
+        if extension.opcode < 8 {
+            return Err(anyhow!(
+                "Opcode {} of {} is a standard opcode",
+                extension.opcode,
+                extension.mnemonic
+            ));
+        }
+        // the assembler would always pick the standard instruction
+        if STANDARD_MNEMONICS
+            .iter()
+            .any(|mnemonic| mnemonic.eq_ignore_ascii_case(extension.mnemonic))
+        {
+            return Err(anyhow!(
+                "Mnemonic {} of opcode {} is a standard mnemonic",
+                extension.mnemonic,
+                extension.opcode
+            ));
+        }
+        if let Some(existing) = self.extensions.iter().find(|e| {
+            e.opcode == extension.opcode || e.mnemonic.eq_ignore_ascii_case(extension.mnemonic)
+        }) {
+            return Err(anyhow!(
+                "{} ({}) conflicts with {} ({})",
+                extension.mnemonic,
+                extension.opcode,
+                existing.mnemonic,
+                existing.opcode
+            ));
+        }
+        self.extensions.push(extension);
+        Ok(self)
+    }
+
+    pub fn with_reserved_combo(mut self, reserved_combo: ReservedCombo) -> Self {
+        self.reserved_combo = reserved_combo;
+        self
+    }
+
+    pub fn reserved_combo(&self) -> ReservedCombo {
+        self.reserved_combo
+    }
+
+    pub fn extension(&self, mnemonic: &str) -> Option<&'static Extension> {
+        self.extensions
+            .iter()
+            .find(|e| e.mnemonic.eq_ignore_ascii_case(mnemonic))
+            .copied()
+    }
+
+    pub fn decode(&self, opcode: u8, operand: u8) -> Result<Instruction> {
+        let mut instruction = match self.extensions.iter().find(|e| e.opcode == opcode) {
+            Some(extension) => Instruction::Extended(
+                extension,
+                match extension.operand {
+                    OperandKind::Combo => Operand::Combo(Combo::parse(operand)?),
+                    OperandKind::Literal | OperandKind::Ignored => {
+                        Operand::Literal(Literal::parse(operand)?)
+                    }
+                },
+            ),
+            None => Instruction::parse(opcode, operand)?,
+        };
+
+        if let Some(combo @ Combo::Reserved) = instruction.combo_mut() {
+            if self.reserved_combo == ReservedCombo::RegisterD {
+                *combo = Combo::Register(Register::D);
+            }
+        }
+
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        interpreter::run,
+        parse::{format_input, parse_str_with_isa},
+    };
+
+    use super::*;
+
+    /// c = operand squared
+    static SQR: Extension = Extension {
+        opcode: 12,
+        mnemonic: "sqr",
+        operand: OperandKind::Literal,
+        execute: |state, operand| {
+            state.registers.c = operand * operand;
+            Ok(Flow::Next)
+        },
+    };
+
+    #[test]
+    fn runs_experimental_program() -> Result<()> {
+        // dst(A), mul(D), out(A), hlt(), out(D)
+        let input = "Register A: 3\nRegister B: 0\nRegister C: 0\n\nProgram: 10,4,8,7,5,4,9,0,5,7";
+        let isa = Isa::experimental();
+        let (program, mut state) = parse_str_with_isa(input, &isa)?;
+
+        assert_eq!("<dst(A),mul(D),out(A),hlt(),out(D)>", program.to_string());
+        run(&program, &mut state, &Default::default())?;
+
+        assert_eq!(vec![1], state.out);
+        assert_eq!(9, state.registers.a);
+        assert_eq!(10, state.pc);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_what_it_decodes() -> Result<()> {
+        let input = "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 12,5,0,7,5,7\n";
+        let isa = Isa::standard().with_extension(&SQR)?;
+
+        let (program, state) = parse_str_with_isa(input, &isa)?;
+
+        assert_eq!("<sqr(5),adv(7),out(7)>", program.to_string());
+        assert_eq!(input, format_input(&program, &state.registers));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_conflicting_extensions() {
+        assert!(Isa::standard().with_extension(&SQR).is_ok());
+        assert!(Isa::experimental().with_extension(&MUL).is_err());
+
+        static SHADOW: Extension = Extension {
+            opcode: 3,
+            mnemonic: "jmp",
+            operand: OperandKind::Literal,
+            execute: |_, operand| Ok(Flow::Jump(operand as usize)),
+        };
+        assert!(Isa::standard().with_extension(&SHADOW).is_err());
+
+        static CLASH: Extension = Extension {
+            opcode: 12,
+            mnemonic: "OUT",
+            operand: OperandKind::Combo,
+            execute: |_, _| Ok(Flow::Next),
+        };
+        assert!(Isa::standard().with_extension(&CLASH).is_err());
+    }
+
+    #[test]
+    fn standard_isa_rejects_extended_opcodes() {
+        let input = "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 8,4";
+
+        assert!(parse_str_with_isa(input, &Isa::standard()).is_err());
+    }
+}
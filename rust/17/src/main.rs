@@ -1,137 +1,272 @@
-use anyhow::{Error, Ok, Result};
-use instructions::Instruction;
-use interpreter::step;
-use parse::parse_input;
-use program::Program;
-use state::{RegisterState, State};
-use std::{
-    collections::{HashMap, HashSet},
-    env,
-};
+use anyhow::{anyhow, Ok, Result};
+use assembler::assemble;
+use bruteforce::{Goal, SearchOptions};
+use cfg::ControlFlowGraph;
+use compiled::CompiledProgram;
+use debugger::Debugger;
+use decompiler::decompile;
+use interpreter::{run, run_with_register_a, Limits};
+use isa::Isa;
+use parse::{format_input, parse_input, parse_input_with_isa};
+use pipeline::Pipeline;
+use solver::solve;
+use std::{env, fs::read_to_string, io, time::Instant};
 
+mod assembler;
+mod bruteforce;
+mod cfg;
+mod compiled;
+mod debugger;
+mod decompiler;
 mod instructions;
 mod interpreter;
+mod isa;
 mod parse;
+mod pipeline;
 mod program;
+mod sat;
+mod solver;
 mod state;
+mod symbolic;
+mod watch;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let part: u8 = args
-        .get(1)
-        .expect("Please specify which part to execute (1 or 2 or 3)")
-        .parse()?;
+    let mut args: Vec<String> = env::args().collect();
+    let isa = take_isa_option(&mut args)?;
+    let mode = args.get(1).expect(
+        "Please specify which mode to execute (1, 2, 3, debug, assemble, solve, symbolic, sat, cfg, decompile, bench, bruteforce or pipeline)",
+    );
     let file_path = args.get(2).expect("Please provide a file path");
 
-    if part == 1 {
-        let result = part_one(file_path)?;
-        let result: Vec<String> = result.iter().map(|n| n.to_string()).collect();
-        println!("Out: {}", result.join(","));
-    } else if part == 2 {
-        let a = part_two(file_path)?;
-        println!("Register A: {}", a);
-    } else {
-        let a = args.get(3).expect("Please provide a register a").parse()?;
-        let (program, _) = parse_input(file_path)?;
-        let out = run_with_register_a(&program, a)?;
-        println!("Result for {:b}: {:?}", a, out);
+    match mode.as_str() {
+        "1" => {
+            let limits = parse_limits(&args[3..])?;
+            let result = part_one(file_path, &isa, &limits)?;
+            let result: Vec<String> = result.iter().map(|n| n.to_string()).collect();
+            println!("Out: {}", result.join(","));
+        }
+        "2" => {
+            let a = part_two(file_path)?;
+            println!("Register A: {}", a);
+        }
+        "3" => {
+            let a = args.get(3).expect("Please provide a register a").parse()?;
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            let out = run_with_register_a(&program, a)?;
+            println!("Result for {:b}: {:?}", a, out);
+        }
+        "debug" => {
+            let limits = parse_limits(&args[3..])?;
+            let (program, state) = parse_input_with_isa(file_path, &isa)?;
+            println!("Program: {}", program);
+            println!("Type `help` for a list of commands");
+            let mut debugger = Debugger::new(&program, state, limits);
+            debugger.run(io::stdin().lock(), &mut io::stdout())?;
+            println!(
+                "Final state: pc: {} {}",
+                debugger.state().pc,
+                debugger.state().registers
+            );
+        }
+        "assemble" => {
+            let (program, registers) = assemble(&read_to_string(file_path)?, &isa)?;
+            print!("{}", format_input(&program, &registers));
+        }
+        "solve" | "sat" => {
+            let target = args
+                .get(3)
+                .expect("Please provide a comma separated target output")
+                .split(',')
+                .map(|n| n.trim().parse())
+                .collect::<Result<Vec<u8>, _>>()?;
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            let a = if mode == "sat" {
+                symbolic::solve(&program, &target)?
+            } else {
+                solve(&program, &target)?
+            };
+            println!("Register A: {}", a);
+        }
+        "symbolic" => {
+            let bits = args
+                .get(3)
+                .expect("Please provide the number of unknown bits of register a")
+                .parse()?;
+            let max_outputs = match args.get(4) {
+                Some(n) => n.parse()?,
+                None => 64,
+            };
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            print!("{}", symbolic::explore(&program, bits, None, max_outputs)?);
+        }
+        "cfg" => {
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            let cfg = ControlFlowGraph::new(&program);
+            if args.get(3).is_some_and(|format| format == "dot") {
+                print!("{}", cfg.to_dot());
+            } else {
+                print!("{}", cfg);
+            }
+        }
+        "decompile" => {
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            print!("{}", decompile(&program));
+        }
+        "bench" => {
+            let count = match args.get(3) {
+                Some(n) => n.parse()?,
+                None => 100_000,
+            };
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            bench(&program, count)?;
+        }
+        "bruteforce" => {
+            let goal = Goal::parse(
+                args.get(3)
+                    .expect("Please provide a target output, prefix:.., suffix:.. or length:.."),
+            )?;
+            let mut options = SearchOptions::default();
+            if let Some(range) = args.get(4) {
+                let (start, end) = range
+                    .split_once("..")
+                    .ok_or(anyhow!("Range must look like start..end"))?;
+                options.range = start.parse()?..end.parse()?;
+            }
+            options.checkpoint = args.get(5).map(Into::into);
+            let (program, _) = parse_input_with_isa(file_path, &isa)?;
+            match bruteforce::search(&program, &options, |out| goal.matches(out))? {
+                Some(a) => println!("Register A: {}", a),
+                None => println!("No register A in {:?} matches", options.range),
+            }
+        }
+        "pipeline" => {
+            let mut paths = vec![file_path.clone()];
+            let mut input = Vec::new();
+            let mut feedback = false;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--feedback" => feedback = true,
+                    "--input" => {
+                        input = rest
+                            .next()
+                            .ok_or(anyhow!("Missing value for --input"))?
+                            .split(',')
+                            .map(|n| n.trim().parse())
+                            .collect::<Result<Vec<u8>, _>>()?;
+                    }
+                    path => paths.push(path.to_string()),
+                }
+            }
+            let machines = paths
+                .iter()
+                .map(|path| parse_input_with_isa(path, &isa))
+                .collect::<Result<Vec<_>>>()?;
+            let mut machines: Vec<_> = machines
+                .iter()
+                .map(|(program, state)| (program, state.clone()))
+                .collect();
+            machines[0].1.input.extend(input);
+
+            let mut pipeline = Pipeline::new(machines, feedback);
+            let out = pipeline.run(&Limits::default())?;
+            for (index, state) in pipeline.states().enumerate() {
+                println!("Machine {}: {}", index, state.registers);
+            }
+            let out: Vec<String> = out.iter().map(|n| n.to_string()).collect();
+            println!("Out: {}", out.join(","));
+        }
+        _ => return Err(anyhow!("Unknown mode {}", mode)),
     }
 
     Ok(())
 }
 
-fn part_one(path: &str) -> Result<Vec<u8>> {
-    let (program, mut state) = parse_input(path)?;
+fn part_one(path: &str, isa: &Isa, limits: &Limits) -> Result<Vec<u8>> {
+    let (program, mut state) = parse_input_with_isa(path, isa)?;
 
-    while program.valid_pc(state.pc) {
-        step(&program, &mut state)?;
-    }
+    run(&program, &mut state, limits)?;
 
     Ok(state.out)
 }
 
-fn part_two(path: &str) -> Result<u64> {
-    /*
-    This particular program essentially has following loop:
-    do:
-        ...
-        out ...
-        a = a >> 3;
-    while (a > 0)
-
-    Thus, we can find a particular input by starting from the minimum.
-    For instance, we have following property:
-    - a=0b101 yields [7]
-    - a=0b101000 yields [2, 7]
-    - a=0b101000000 yields [4, 2, 7]
-    ...
-    So shifting a value 3 bits to the left does not modify the last entries
-     */
-    let (program, _) = parse_input(path)?;
-    println!("Program: {}", program);
-    let expected_out: Vec<u8> = program
-        .iter_instructions()
-        .map(<&Instruction as Into<(u8, u8)>>::into)
-        .fold(Vec::new(), |mut vec, (opcode, operand)| {
-            vec.push(opcode);
-            vec.push(operand);
-            vec
-        });
-    let out_length: u32 = expected_out.len().try_into()?;
-
-    // maps n to a set of numbers that produce the correct result
-    // when considering the last n elements
-    let mut possible_prefixes: HashMap<u32, HashSet<u64>> = HashMap::new();
-    possible_prefixes.insert(0, HashSet::new());
-    possible_prefixes.entry(0).and_modify(|s| {
-        s.insert(0);
-    });
-
-    let mut solutions = Vec::new();
-
-    for n in 0..out_length {
-        let prefixes = possible_prefixes.get(&n).unwrap();
-        let mut new_prefixes = HashSet::new();
-        let index: usize = (out_length - n - 1).try_into()?;
-        let expected_value = expected_out.get(index).unwrap();
-
-        for prefix in prefixes {
-            for i in 0..8 {
-                let test_a = prefix + i;
-                let out = run_with_register_a(&program, test_a)?;
-
-                if expected_out == out {
-                    solutions.push(test_a);
-                }
-                if expected_value == out.first().unwrap() {
-                    new_prefixes.insert(test_a << 3);
-                }
-            }
-        }
-        possible_prefixes.insert(n + 1, new_prefixes);
-    }
+/// runs the program for register A in 0..count with the interpreter and the compiled program
+fn bench(program: &program::Program, count: u64) -> Result<()> {
+    let start = Instant::now();
+    let interpreted = (0..count)
+        .map(|a| run_with_register_a(program, a))
+        .collect::<Result<Vec<_>>>()?;
+    let interpreter_time = start.elapsed();
 
-    if solutions.is_empty() {
-        Err(Error::msg("Could not find a solution"))
-    } else {
-        Ok(*solutions.iter().min().unwrap())
+    let start = Instant::now();
+    let compiled_program = CompiledProgram::new(program);
+    let compiled = (0..count)
+        .map(|a| compiled_program.run_with_register_a(a))
+        .collect::<Result<Vec<_>, _>>()?;
+    let compiled_time = start.elapsed();
+
+    if interpreted != compiled {
+        return Err(anyhow!("Compiled program produced a different output"));
     }
+    println!("Runs:        {}", count);
+    println!("Interpreter: {:?}", interpreter_time);
+    println!("Compiled:    {:?}", compiled_time);
+    println!(
+        "Speedup:     {:.2}x",
+        interpreter_time.as_secs_f64() / compiled_time.as_secs_f64()
+    );
+
+    Ok(())
 }
 
-fn run_with_register_a(program: &Program, a: u64) -> Result<Vec<u8>> {
-    let mut state = State {
-        registers: RegisterState {
-            a,
-            ..Default::default()
-        },
-        ..Default::default()
+/// removes `--isa <standard|experimental>` from the arguments, defaulting to standard
+fn take_isa_option(args: &mut Vec<String>) -> Result<Isa> {
+    let Some(index) = args.iter().position(|arg| arg == "--isa") else {
+        return Ok(Isa::standard());
     };
+    let name = args
+        .get(index + 1)
+        .ok_or(anyhow!("Missing value for --isa"))?
+        .clone();
+    args.drain(index..index + 2);
 
-    while program.valid_pc(state.pc) {
-        step(program, &mut state)?;
+    match name.as_str() {
+        "standard" => Ok(Isa::standard()),
+        "experimental" => Ok(Isa::experimental()),
+        _ => Err(anyhow!("Unknown instruction set {}", name)),
     }
+}
 
-    Ok(state.out)
+/// parses `--max-steps <n|none>`, `--max-output <n|none>` and `--detect-cycles`
+fn parse_limits(args: &[String]) -> Result<Limits> {
+    let mut limits = Limits::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" | "--max-output" => {
+                let value = args.next().ok_or(anyhow!("Missing value for {}", arg))?;
+                let value = match value.as_str() {
+                    "none" => None,
+                    n => Some(n.parse()?),
+                };
+                if arg == "--max-steps" {
+                    limits.max_steps = value;
+                } else {
+                    limits.max_output = value;
+                }
+            }
+            "--detect-cycles" => limits.detect_cycles = true,
+            _ => return Err(anyhow!("Unknown option {}", arg)),
+        }
+    }
+    Ok(limits)
+}
+
+fn part_two(path: &str) -> Result<u64> {
+    let (program, _) = parse_input(path)?;
+    println!("Program: {}", program);
+
+    solve(&program, &program.encode())
 }
 
 #[cfg(test)]
@@ -140,7 +275,7 @@ mod tests {
 
     #[test]
     fn sample() -> Result<()> {
-        let result = part_one("sample.txt")?;
+        let result = part_one("sample.txt", &Isa::standard(), &Limits::default())?;
 
         assert_eq!(vec![4, 6, 3, 5, 6, 3, 5, 2, 1, 0], result);
 
@@ -149,7 +284,7 @@ mod tests {
 
     #[test]
     fn input() -> Result<()> {
-        let result = part_one("input.txt")?;
+        let result = part_one("input.txt", &Isa::standard(), &Limits::default())?;
 
         assert_eq!(vec![7, 0, 7, 3, 4, 1, 3, 0, 1], result);
 
@@ -1,16 +1,31 @@
 use std::fs::read_to_string;
 
-use anyhow::{Context, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use regex::Regex;
 
 use crate::{
-    instructions::Instruction,
+    isa::Isa,
     program::Program,
     state::{RegisterState, State},
 };
 
 pub fn parse_input(path: &str) -> Result<(Program, State)> {
     let input = read_to_string(path)?;
+    parse_str(&input)
+}
+
+pub fn parse_input_with_isa(path: &str, isa: &Isa) -> Result<(Program, State)> {
+    let input = read_to_string(path)?;
+    parse_str_with_isa(&input, isa)
+}
+
+pub fn parse_str(input: &str) -> Result<(Program, State)> {
+    parse_str_with_isa(input, &Isa::standard())
+}
+
+/// Like `parse_str`, decoding opcodes with the given instruction set. An optional
+/// `Register D` line may follow the three standard registers.
+pub fn parse_str_with_isa(input: &str, isa: &Isa) -> Result<(Program, State)> {
     let mut instructions = Vec::new();
 
     let program = input
@@ -18,18 +33,18 @@ pub fn parse_input(path: &str) -> Result<(Program, State)> {
         .skip_while(|line| !line.is_empty())
         .nth(1)
         .context("Could not find program in input file")?;
-    let instr_regex = Regex::new(r"(\d),(\d)").unwrap();
+    let instr_regex = Regex::new(r"(\d+),(\d+)").unwrap();
     for capture in instr_regex.captures_iter(program) {
         let (_, [opcode, operand]) = capture.extract();
         let opcode = opcode.parse()?;
         let operand = operand.parse()?;
-        let instruction = Instruction::parse(opcode, operand)?;
+        let instruction = isa.decode(opcode, operand)?;
         instructions.push(instruction);
     }
 
-    let [a, b, c] = input
+    let registers = input
         .lines()
-        .take(3)
+        .take_while(|line| !line.is_empty())
         .map(|line| {
             let n: u64 = line
                 .split(": ")
@@ -38,14 +53,51 @@ pub fn parse_input(path: &str) -> Result<(Program, State)> {
                 .parse()?;
             Ok::<u64, Error>(n)
         })
-        .collect::<Result<Vec<u64>>>()?
-        .try_into()
-        .expect("Could not parse registers");
+        .collect::<Result<Vec<u64>>>()?;
+    let registers = match registers[..] {
+        [a, b, c] => RegisterState { a, b, c, d: 0 },
+        [a, b, c, d] => RegisterState { a, b, c, d },
+        _ => {
+            return Err(anyhow!(
+                "Expected 3 or 4 registers, found {}",
+                registers.len()
+            ))
+        }
+    };
     let state = State {
         pc: 0,
-        out: Vec::new(),
-        registers: RegisterState { a, b, c },
+        registers,
+        ..Default::default()
     };
 
     Ok((Program::new(instructions), state))
 }
+
+/// inverse of `parse_str`
+pub fn format_input(program: &Program, registers: &RegisterState) -> String {
+    let numbers: Vec<String> = program.encode().iter().map(|n| n.to_string()).collect();
+    let mut input = format!(
+        "Register A: {}\nRegister B: {}\nRegister C: {}\n",
+        registers.a, registers.b, registers.c
+    );
+    if registers.d != 0 {
+        input.push_str(&format!("Register D: {}\n", registers.d));
+    }
+    input + &format!("\nProgram: {}\n", numbers.join(","))
+}
+
+/// Program of comma separated numbers, decoded with the standard instruction set
+#[cfg(test)]
+pub fn program(numbers: &str) -> Result<Program> {
+    Ok(machine(0, numbers, &Isa::standard())?.0)
+}
+
+/// Program of comma separated numbers and a state with register A set to `a`
+#[cfg(test)]
+pub fn machine(a: u64, numbers: &str, isa: &Isa) -> Result<(Program, State)> {
+    let input = format!(
+        "Register A: {}\nRegister B: 0\nRegister C: 0\n\nProgram: {}",
+        a, numbers
+    );
+    parse_str_with_isa(&input, isa)
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    interpreter::{run_until_blocked, Limits, Status},
+    program::Program,
+    state::State,
+};
+
+/// Machines wired in a row, each one's output feeding the next one's input.
+///
+/// Machines run in turns until they halt or wait for input. With feedback,
+/// the output of the last machine is fed back into the first one.
+pub struct Pipeline<'a> {
+    machines: Vec<Machine<'a>>,
+    feedback: bool,
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    state: State,
+    /// number of outputs already passed on to the next machine
+    forwarded: usize,
+    status: Status,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(machines: Vec<(&'a Program, State)>, feedback: bool) -> Self {
+        let machines = machines
+            .into_iter()
+            .map(|(program, state)| Machine {
+                program,
+                state,
+                forwarded: 0,
+                status: Status::Running,
+            })
+            .collect();
+
+        Pipeline { machines, feedback }
+    }
+
+    /// Runs until every machine halted and returns the output of the last one
+    /// that was not fed back. Fails if all remaining machines wait for input.
+    pub fn run(&mut self, limits: &Limits) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+
+        loop {
+            let mut progress = false;
+            for index in 0..self.machines.len() {
+                let machine = &mut self.machines[index];
+                if machine.status == Status::Halted {
+                    continue;
+                }
+
+                let pc = machine.state.pc;
+                let consumed = machine.state.input.len();
+                machine.status = run_until_blocked(machine.program, &mut machine.state, limits)?;
+                progress |= machine.state.pc != pc
+                    || machine.state.input.len() != consumed
+                    || machine.status == Status::Halted;
+
+                let output = machine.state.out[machine.forwarded..].to_vec();
+                machine.forwarded = machine.state.out.len();
+                match self.next(index) {
+                    Some(next) => self.machines[next].state.input.extend(output),
+                    None => result.extend(output),
+                }
+            }
+
+            if self.machines.iter().all(|m| m.status == Status::Halted) {
+                return Ok(result);
+            }
+            if !progress {
+                let waiting: Vec<String> = self
+                    .machines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| m.status == Status::Blocked)
+                    .map(|(index, m)| format!("machine {} at pc {}", index, m.state.pc))
+                    .collect();
+                return Err(anyhow!(
+                    "Deadlock, waiting for input: {}",
+                    waiting.join(", ")
+                ));
+            }
+        }
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = &State> {
+        self.machines.iter().map(|m| &m.state)
+    }
+
+    /// machine receiving the output of the machine at index
+    fn next(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.machines.len() {
+            Some(index + 1)
+        } else if self.feedback {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{isa::Isa, parse};
+
+    use super::*;
+
+    fn machine(a: u64, numbers: &str) -> Result<(Program, State)> {
+        parse::machine(a, numbers, &Isa::experimental())
+    }
+
+    #[test]
+    fn passes_outputs_along() -> Result<()> {
+        // outputs 7, 1, 2
+        let (producer, producer_state) = machine(0, "1,7,5,5,1,6,5,5,1,3,5,5")?;
+        // adds one to each of three inputs: inp(), bxl(1), out(B), adv(1), jnz(0)
+        let (consumer, consumer_state) = machine(4, "11,0,1,1,5,5,0,1,3,0")?;
+
+        let mut pipeline = Pipeline::new(
+            vec![(&producer, producer_state), (&consumer, consumer_state)],
+            false,
+        );
+
+        assert_eq!(vec![6, 0, 3], pipeline.run(&Limits::default())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn feeds_back_into_first_machine() -> Result<()> {
+        // inp(), out(B), adv(1), jnz(0) forwards two values
+        let (forward, mut first) = machine(2, "11,0,5,5,0,1,3,0")?;
+        let second = first.clone();
+        first.input.push_back(5);
+
+        let mut pipeline = Pipeline::new(vec![(&forward, first), (&forward, second)], true);
+
+        assert!(pipeline.run(&Limits::default())?.is_empty());
+        let outputs: Vec<&Vec<u8>> = pipeline.states().map(|s| &s.out).collect();
+        assert_eq!(vec![&vec![5, 5], &vec![5, 5]], outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_deadlock() -> Result<()> {
+        let (waiting, state) = machine(0, "11,0")?;
+
+        let mut pipeline = Pipeline::new(vec![(&waiting, state.clone()), (&waiting, state)], true);
+
+        let error = pipeline
+            .run(&Limits::default())
+            .expect_err("Should deadlock");
+        assert_eq!(
+            "Deadlock, waiting for input: machine 0 at pc 0, machine 1 at pc 0",
+            error.to_string()
+        );
+
+        Ok(())
+    }
+}
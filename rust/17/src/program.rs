@@ -22,6 +22,23 @@ impl Program {
     pub fn valid_pc(&self, pc: usize) -> bool {
         pc / 2 < self.instructions.len()
     }
+
+    /// pc directly behind the last instruction, where the program halts
+    pub fn end_pc(&self) -> usize {
+        self.instructions.len() * 2
+    }
+
+    /// opcodes and operands in the order they appear in the input file
+    pub fn encode(&self) -> Vec<u8> {
+        self.instructions
+            .iter()
+            .map(<&Instruction as Into<(u8, u8)>>::into)
+            .fold(Vec::new(), |mut vec, (opcode, operand)| {
+                vec.push(opcode);
+                vec.push(operand);
+                vec
+            })
+    }
 }
 
 impl Display for Program {
@@ -0,0 +1,261 @@
+use std::ops::Not;
+
+/// Variable or its negation, encoded as `variable * 2 + negated`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lit(usize);
+
+impl Lit {
+    pub fn positive(variable: usize) -> Self {
+        Lit(variable * 2)
+    }
+
+    pub fn variable(&self) -> usize {
+        self.0 / 2
+    }
+
+    fn is_negated(&self) -> bool {
+        self.0 % 2 == 1
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Self::Output {
+        Lit(self.0 ^ 1)
+    }
+}
+
+#[derive(Default)]
+pub struct Cnf {
+    variables: usize,
+    clauses: Vec<Vec<Lit>>,
+}
+
+impl Cnf {
+    pub fn new_variable(&mut self) -> usize {
+        self.variables += 1;
+        self.variables - 1
+    }
+
+    pub fn add_clause(&mut self, clause: Vec<Lit>) {
+        self.clauses.push(clause);
+    }
+}
+
+/// DPLL with unit propagation and chronological backtracking.
+///
+/// Variables are decided in `decision_order` (then all remaining ones), trying false
+/// before true, so the first model found is the lexicographically smallest one
+/// with respect to that order.
+pub fn solve(cnf: &Cnf, decision_order: &[usize]) -> Option<Vec<bool>> {
+    let mut solver = Solver::new(cnf);
+    let mut order = decision_order.to_vec();
+    let mut ordered = vec![false; cnf.variables];
+    for variable in decision_order {
+        ordered[*variable] = true;
+    }
+    order.extend((0..cnf.variables).filter(|v| !ordered[*v]));
+
+    for clause in &cnf.clauses {
+        match clause.as_slice() {
+            [] => return None,
+            [lit] if !solver.assign(*lit) => return None,
+            _ => {}
+        }
+    }
+
+    // (trail length before the decision, decided literal, whether it was flipped already)
+    let mut decisions: Vec<(usize, Lit, bool)> = Vec::new();
+    loop {
+        if !solver.propagate() {
+            loop {
+                let (trail_length, lit, flipped) = decisions.pop()?;
+                solver.backtrack(trail_length);
+                if !flipped {
+                    decisions.push((trail_length, !lit, true));
+                    solver.assign(!lit);
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let Some(variable) = order.iter().find(|v| solver.assignment[**v].is_none()) else {
+            return Some(
+                solver
+                    .assignment
+                    .iter()
+                    .map(|value| value.expect("All variables are assigned"))
+                    .collect(),
+            );
+        };
+        let lit = !Lit::positive(*variable);
+        decisions.push((solver.trail.len(), lit, false));
+        solver.assign(lit);
+    }
+}
+
+struct Solver<'a> {
+    cnf: &'a Cnf,
+    /// for every literal, the clauses that contain it
+    occurrences: Vec<Vec<usize>>,
+    assignment: Vec<Option<bool>>,
+    trail: Vec<Lit>,
+    propagated: usize,
+}
+
+impl<'a> Solver<'a> {
+    fn new(cnf: &'a Cnf) -> Self {
+        let mut occurrences = vec![Vec::new(); cnf.variables * 2];
+        for (index, clause) in cnf.clauses.iter().enumerate() {
+            for lit in clause {
+                occurrences[lit.0].push(index);
+            }
+        }
+
+        Solver {
+            cnf,
+            occurrences,
+            assignment: vec![None; cnf.variables],
+            trail: Vec::new(),
+            propagated: 0,
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.assignment[lit.variable()].map(|value| value != lit.is_negated())
+    }
+
+    /// returns false if the literal is already false
+    fn assign(&mut self, lit: Lit) -> bool {
+        match self.value(lit) {
+            Some(value) => value,
+            None => {
+                self.assignment[lit.variable()] = Some(!lit.is_negated());
+                self.trail.push(lit);
+                true
+            }
+        }
+    }
+
+    /// returns false on a conflict
+    fn propagate(&mut self) -> bool {
+        while self.propagated < self.trail.len() {
+            let falsified = !self.trail[self.propagated];
+            self.propagated += 1;
+
+            for clause_index in &self.occurrences[falsified.0] {
+                let mut unassigned = None;
+                let mut unassigned_count = 0;
+                let mut satisfied = false;
+                for lit in &self.cnf.clauses[*clause_index] {
+                    match self.value(*lit) {
+                        Some(true) => {
+                            satisfied = true;
+                            break;
+                        }
+                        Some(false) => {}
+                        None => {
+                            unassigned = Some(*lit);
+                            unassigned_count += 1;
+                        }
+                    }
+                }
+
+                match (satisfied, unassigned_count, unassigned) {
+                    (true, _, _) => {}
+                    (false, 0, _) => return false,
+                    (false, 1, Some(lit)) => {
+                        self.assignment[lit.variable()] = Some(!lit.is_negated());
+                        self.trail.push(lit);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        true
+    }
+
+    fn backtrack(&mut self, trail_length: usize) {
+        for lit in self.trail.drain(trail_length..) {
+            self.assignment[lit.variable()] = None;
+        }
+        self.propagated = trail_length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cnf(variables: usize, clauses: &[&[i32]]) -> Cnf {
+        let mut cnf = Cnf::default();
+        for _ in 0..variables {
+            cnf.new_variable();
+        }
+        for clause in clauses {
+            cnf.add_clause(
+                clause
+                    .iter()
+                    .map(|n| {
+                        let lit = Lit::positive(n.unsigned_abs() as usize - 1);
+                        if *n < 0 {
+                            !lit
+                        } else {
+                            lit
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        cnf
+    }
+
+    #[test]
+    fn finds_smallest_model() {
+        // x1 | x2, !x1 | x3
+        let cnf = cnf(3, &[&[1, 2], &[-1, 3]]);
+
+        assert_eq!(Some(vec![false, true, false]), solve(&cnf, &[0, 1, 2]));
+        assert_eq!(Some(vec![true, false, true]), solve(&cnf, &[1, 0, 2]));
+    }
+
+    #[test]
+    fn detects_unsatisfiable() {
+        let cnf = cnf(2, &[&[1, 2], &[-1, 2], &[1, -2], &[-1, -2]]);
+
+        assert_eq!(None, solve(&cnf, &[]));
+        assert_eq!(None, solve(&cnf_with_empty_clause(), &[]));
+    }
+
+    #[test]
+    fn respects_unit_clauses() {
+        let cnf = cnf(3, &[&[3], &[-3, -1], &[1, 2]]);
+
+        assert_eq!(Some(vec![false, true, true]), solve(&cnf, &[0, 1, 2]));
+    }
+
+    #[test]
+    fn solves_pigeonhole_unsat() {
+        // 3 pigeons into 2 holes, variable p*2 + h + 1 means pigeon p sits in hole h
+        let mut clauses: Vec<Vec<i32>> = (0..3).map(|p| vec![p * 2 + 1, p * 2 + 2]).collect();
+        for h in 0..2 {
+            for p in 0..3 {
+                for q in p + 1..3 {
+                    clauses.push(vec![-(p * 2 + h + 1), -(q * 2 + h + 1)]);
+                }
+            }
+        }
+        let clauses: Vec<&[i32]> = clauses.iter().map(|c| c.as_slice()).collect();
+
+        assert_eq!(None, solve(&cnf(6, &clauses), &[]));
+    }
+
+    fn cnf_with_empty_clause() -> Cnf {
+        let mut cnf = Cnf::default();
+        cnf.add_clause(vec![]);
+        cnf
+    }
+}
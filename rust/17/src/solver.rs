@@ -0,0 +1,247 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+
+use crate::{
+    compiled::CompiledProgram,
+    instructions::{Combo, Instruction, Literal, Register},
+    program::Program,
+};
+
+/// Finds the minimal register A for which the program outputs `target`.
+///
+/// Works for programs with following loop:
+/// do:
+///     ...
+///     out ...
+///     a = a >> k;
+/// while (a != 0)
+///
+/// Every iteration outputs one value and drops the last k bits of a, so the
+/// last output only depends on the highest k bits, the second to last output on
+/// the highest 2k bits and so on. We therefore build a from the highest bits
+/// downwards, trying all 2^k values for the next lower bits and only descending
+/// while the program output matches the end of the target.
+pub fn solve(program: &Program, target: &[u8]) -> Result<u64> {
+    let shift = analyze_loop(program)?;
+    if target.is_empty() {
+        return Err(SolveError::NoSolution.into());
+    }
+
+    let compiled = CompiledProgram::new(program);
+    search(&compiled, target, shift, target.len() - 1, 0)?.ok_or(SolveError::NoSolution.into())
+}
+
+#[derive(Debug)]
+pub enum SolveError {
+    UnsupportedStructure(String),
+    NoSolution,
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::UnsupportedStructure(reason) => {
+                write!(
+                    f,
+                    "Program does not have the supported loop shape: {}",
+                    reason
+                )
+            }
+            SolveError::NoSolution => write!(f, "No register A produces the target output"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// tries prefix followed by every k-bit suffix, in increasing order,
+/// so the first solution found is the minimal one
+fn search(
+    program: &CompiledProgram,
+    target: &[u8],
+    shift: u32,
+    index: usize,
+    prefix: u64,
+) -> Result<Option<u64>> {
+    let Some(shifted_prefix) = prefix.checked_mul(1 << shift) else {
+        return Ok(None);
+    };
+
+    for suffix in 0..(1 << shift) {
+        let a = shifted_prefix + suffix;
+        if program.run_with_register_a(a)? != target[index..] {
+            continue;
+        }
+        if index == 0 {
+            return Ok(Some(a));
+        }
+        if let Some(solution) = search(program, target, shift, index - 1, a)? {
+            return Ok(Some(solution));
+        }
+    }
+
+    Ok(None)
+}
+
+/// returns by how many bits a is shifted per iteration
+fn analyze_loop(program: &Program) -> Result<u32, SolveError> {
+    let unsupported = |reason: &str| SolveError::UnsupportedStructure(reason.to_string());
+    let instructions: Vec<&Instruction> = program.iter_instructions().collect();
+
+    let Some((Instruction::Jnz(Literal(0)), body)) = instructions.split_last() else {
+        return Err(unsupported("the last instruction must be jnz(0)"));
+    };
+    if body.iter().any(|i| matches!(i, Instruction::Jnz(_))) {
+        return Err(unsupported("only the last instruction may jump"));
+    }
+    if let Some(extended) = body.iter().find(|i| matches!(i, Instruction::Extended(..))) {
+        return Err(unsupported(&format!(
+            "{} is not a standard instruction",
+            extended
+        )));
+    }
+    if body
+        .iter()
+        .filter(|i| matches!(i, Instruction::Out(_)))
+        .count()
+        != 1
+    {
+        return Err(unsupported("the loop must output exactly once"));
+    }
+
+    let mut shift = None;
+    for instruction in body {
+        if let Instruction::Adv(operand) = instruction {
+            match (operand, shift) {
+                (Combo::Literal(k @ 1..=3), None) => shift = Some(u32::from(*k)),
+                (Combo::Literal(0), _) => return Err(unsupported("adv(0) does not shift a")),
+                (Combo::Register(_) | Combo::Reserved, _) => {
+                    return Err(unsupported("a must be shifted by a literal"))
+                }
+                _ => return Err(unsupported("a must be shifted exactly once")),
+            }
+        }
+    }
+    let shift = shift.ok_or(unsupported("the loop must shift a with adv"))?;
+
+    // outputs may only depend on a, not on values from previous iterations
+    let (mut b_written, mut c_written) = (false, false);
+    for instruction in body {
+        let (reads_b, reads_c) = match instruction {
+            Instruction::Bxl(_) => (true, false),
+            Instruction::Bxc(_) => (true, true),
+            Instruction::Adv(combo)
+            | Instruction::Bst(combo)
+            | Instruction::Out(combo)
+            | Instruction::Bdv(combo)
+            | Instruction::Cdv(combo) => (
+                matches!(combo, Combo::Register(Register::B)),
+                matches!(combo, Combo::Register(Register::C)),
+            ),
+            Instruction::Jnz(_) | Instruction::Extended(..) => (false, false),
+        };
+        if (reads_b && !b_written) || (reads_c && !c_written) {
+            return Err(unsupported(&format!(
+                "{} reads a register before the loop writes it",
+                instruction
+            )));
+        }
+        match instruction {
+            Instruction::Bxl(_)
+            | Instruction::Bst(_)
+            | Instruction::Bxc(_)
+            | Instruction::Bdv(_) => b_written = true,
+            Instruction::Cdv(_) => c_written = true,
+            _ => {}
+        }
+    }
+
+    Ok(shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::run_with_register_a, parse::program};
+
+    use super::*;
+
+    fn solve_error(program: &Program, target: &[u8]) -> SolveError {
+        solve(program, target)
+            .expect_err("Should not find a solution")
+            .downcast()
+            .expect("Should fail with a SolveError")
+    }
+
+    #[test]
+    fn finds_quine_for_sample() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        assert_eq!(117440, solve(&program, &program.encode())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_minimal_a_for_any_target() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        // the output happens after the shift, so the program always ends with 0
+        assert_eq!(0b001_010_000, solve(&program, &[2, 1, 0])?);
+        assert_eq!(0, solve(&program, &[0])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn matches_bruteforce_on_input_like_program() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+
+        for a in [5, 100, 1234, 4321] {
+            let target = run_with_register_a(&program, a)?;
+            let minimal = (0..=a)
+                .find(|a| run_with_register_a(&program, *a).unwrap() == target)
+                .unwrap();
+
+            assert_eq!(minimal, solve(&program, &target)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_missing_solution() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        assert!(matches!(
+            solve_error(&program, &[1]),
+            SolveError::NoSolution
+        ));
+        assert!(matches!(solve_error(&program, &[]), SolveError::NoSolution));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_unsupported_structure() -> Result<()> {
+        let unsupported = |numbers| {
+            matches!(
+                solve_error(&program(numbers).unwrap(), &[0]),
+                SolveError::UnsupportedStructure(_)
+            )
+        };
+
+        // no jump
+        assert!(unsupported("0,3,5,4"));
+        // two outputs
+        assert!(unsupported("0,3,5,4,5,4,3,0"));
+        // no shift
+        assert!(unsupported("2,4,5,5,3,0"));
+        // shift by register
+        assert!(unsupported("0,4,5,4,3,0"));
+        // b is read before it is written
+        assert!(unsupported("1,1,5,5,0,3,3,0"));
+
+        Ok(())
+    }
+}
@@ -1,13 +1,29 @@
-#[derive(Debug, Default)]
+use std::{collections::VecDeque, fmt::Display};
+
+#[derive(Debug, Default, Clone)]
 pub struct State {
     pub pc: usize,
     pub registers: RegisterState,
     pub out: Vec<u8>,
+    /// values read by the `inp` extension
+    pub input: VecDeque<u8>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct RegisterState {
     pub a: u64,
     pub b: u64,
     pub c: u64,
+    /// only used by extended instruction sets
+    pub d: u64,
+}
+
+impl Display for RegisterState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "A: {} B: {} C: {}", self.a, self.b, self.c)?;
+        if self.d != 0 {
+            write!(f, " D: {}", self.d)?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,620 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    instructions::{Combo, Instruction, Literal, Register},
+    program::Program,
+    sat::{self, Cnf, Lit},
+    solver::SolveError,
+};
+
+const WORD_SIZE: usize = 64;
+const MAX_STEPS: usize = 1_000_000;
+
+/// Reference to a node of a `Circuit`, i.e. a boolean function of the input bits
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub struct Bit(usize);
+
+pub const FALSE: Bit = Bit(0);
+pub const TRUE: Bit = Bit(1);
+
+type Word = [Bit; WORD_SIZE];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Constant(bool),
+    Input(usize),
+    Not(Bit),
+    And(Bit, Bit),
+    Or(Bit, Bit),
+    Xor(Bit, Bit),
+}
+
+/// Hash-consed boolean circuit over the bits of register A.
+/// Constants are folded while building, so bits that do not depend on the input
+/// stay `TRUE` or `FALSE`.
+pub struct Circuit {
+    nodes: Vec<Node>,
+    lookup: HashMap<Node, Bit>,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        let mut circuit = Circuit {
+            nodes: Vec::new(),
+            lookup: HashMap::new(),
+        };
+        circuit.insert(Node::Constant(false));
+        circuit.insert(Node::Constant(true));
+        circuit
+    }
+
+    pub fn input(&mut self, index: usize) -> Bit {
+        self.insert(Node::Input(index))
+    }
+
+    pub fn not(&mut self, x: Bit) -> Bit {
+        match self.nodes[x.0] {
+            Node::Constant(value) => self.constant(!value),
+            Node::Not(inner) => inner,
+            _ => self.insert(Node::Not(x)),
+        }
+    }
+
+    pub fn and(&mut self, x: Bit, y: Bit) -> Bit {
+        match (x, y) {
+            (FALSE, _) | (_, FALSE) => FALSE,
+            (TRUE, other) | (other, TRUE) => other,
+            _ if x == y => x,
+            _ if self.are_complements(x, y) => FALSE,
+            _ => self.insert(Node::And(x.min(y), x.max(y))),
+        }
+    }
+
+    pub fn or(&mut self, x: Bit, y: Bit) -> Bit {
+        match (x, y) {
+            (TRUE, _) | (_, TRUE) => TRUE,
+            (FALSE, other) | (other, FALSE) => other,
+            _ if x == y => x,
+            _ if self.are_complements(x, y) => TRUE,
+            _ => self.insert(Node::Or(x.min(y), x.max(y))),
+        }
+    }
+
+    pub fn xor(&mut self, x: Bit, y: Bit) -> Bit {
+        match (x, y) {
+            (FALSE, other) | (other, FALSE) => other,
+            (TRUE, other) | (other, TRUE) => self.not(other),
+            _ if x == y => FALSE,
+            _ if self.are_complements(x, y) => TRUE,
+            _ => self.insert(Node::Xor(x.min(y), x.max(y))),
+        }
+    }
+
+    /// if condition then x else y
+    pub fn mux(&mut self, condition: Bit, x: Bit, y: Bit) -> Bit {
+        if x == y {
+            return x;
+        }
+        let not_condition = self.not(condition);
+        let x = self.and(condition, x);
+        let y = self.and(not_condition, y);
+        self.or(x, y)
+    }
+
+    pub fn constant(&self, value: bool) -> Bit {
+        if value {
+            TRUE
+        } else {
+            FALSE
+        }
+    }
+
+    /// values of all nodes if register A initially holds `a`
+    #[cfg(test)]
+    fn evaluate(&self, a: u64) -> Vec<bool> {
+        let mut values: Vec<bool> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let value = match node {
+                Node::Constant(value) => *value,
+                Node::Input(i) => (a >> i) & 1 == 1,
+                Node::Not(x) => !values[x.0],
+                Node::And(x, y) => values[x.0] && values[y.0],
+                Node::Or(x, y) => values[x.0] || values[y.0],
+                Node::Xor(x, y) => values[x.0] ^ values[y.0],
+            };
+            values.push(value);
+        }
+        values
+    }
+
+    fn insert(&mut self, node: Node) -> Bit {
+        if let Some(bit) = self.lookup.get(&node) {
+            return *bit;
+        }
+        let bit = Bit(self.nodes.len());
+        self.nodes.push(node);
+        self.lookup.insert(node, bit);
+        bit
+    }
+
+    fn are_complements(&self, x: Bit, y: Bit) -> bool {
+        self.nodes[x.0] == Node::Not(y) || self.nodes[y.0] == Node::Not(x)
+    }
+
+    fn word_constant(&self, n: u64) -> Word {
+        std::array::from_fn(|i| self.constant((n >> i) & 1 == 1))
+    }
+
+    fn word_xor(&mut self, x: &Word, y: &Word) -> Word {
+        std::array::from_fn(|i| self.xor(x[i], y[i]))
+    }
+
+    fn word_mod_8(&self, x: &Word) -> Word {
+        std::array::from_fn(|i| if i < 3 { x[i] } else { FALSE })
+    }
+
+    fn word_shift_right_constant(&self, x: &Word, shift: usize) -> Word {
+        std::array::from_fn(|i| *x.get(i + shift).unwrap_or(&FALSE))
+    }
+
+    /// barrel shifter, only valid for shifts below 64, see `shift_in_range`
+    fn word_shift_right(&mut self, x: &Word, shift: &Word) -> Word {
+        let mut result = *x;
+        for (j, condition) in shift.iter().take(6).enumerate() {
+            let shifted = self.word_shift_right_constant(&result, 1 << j);
+            result = std::array::from_fn(|i| self.mux(*condition, shifted[i], result[i]));
+        }
+        result
+    }
+
+    /// the interpreter fails with an overflow when dividing by 2^64 or more
+    fn shift_in_range(&mut self, shift: &Word) -> Bit {
+        let too_large = shift[6..].iter().fold(FALSE, |acc, bit| self.or(acc, *bit));
+        self.not(too_large)
+    }
+
+    fn word_is_nonzero(&mut self, x: &Word) -> Bit {
+        x.iter().fold(FALSE, |acc, bit| self.or(acc, *bit))
+    }
+
+    fn bits_equal_constant(&mut self, bits: &[Bit], n: u8) -> Bit {
+        bits.iter().enumerate().fold(TRUE, |acc, (i, bit)| {
+            let expected = if (n >> i) & 1 == 1 {
+                *bit
+            } else {
+                self.not(*bit)
+            };
+            self.and(acc, expected)
+        })
+    }
+
+    /// Tseitin encoding asserting all roots; returns the cnf and the variable of every input bit
+    fn to_cnf(&self, roots: &[Bit]) -> (Cnf, HashMap<usize, usize>) {
+        let mut cnf = Cnf::default();
+        let mut lits: HashMap<Bit, Lit> = HashMap::new();
+        let mut inputs = HashMap::new();
+
+        for index in self.reachable(roots) {
+            let new_lit = |cnf: &mut Cnf| Lit::positive(cnf.new_variable());
+            let lit = match self.nodes[index] {
+                Node::Constant(value) => {
+                    let y = new_lit(&mut cnf);
+                    cnf.add_clause(vec![if value { y } else { !y }]);
+                    y
+                }
+                Node::Input(i) => {
+                    let y = new_lit(&mut cnf);
+                    inputs.insert(i, y.variable());
+                    y
+                }
+                Node::Not(x) => !lits[&x],
+                Node::And(x1, x2) => {
+                    let (x1, x2, y) = (lits[&x1], lits[&x2], new_lit(&mut cnf));
+                    cnf.add_clause(vec![!y, x1]);
+                    cnf.add_clause(vec![!y, x2]);
+                    cnf.add_clause(vec![y, !x1, !x2]);
+                    y
+                }
+                Node::Or(x1, x2) => {
+                    let (x1, x2, y) = (lits[&x1], lits[&x2], new_lit(&mut cnf));
+                    cnf.add_clause(vec![y, !x1]);
+                    cnf.add_clause(vec![y, !x2]);
+                    cnf.add_clause(vec![!y, x1, x2]);
+                    y
+                }
+                Node::Xor(x1, x2) => {
+                    let (x1, x2, y) = (lits[&x1], lits[&x2], new_lit(&mut cnf));
+                    cnf.add_clause(vec![!y, x1, x2]);
+                    cnf.add_clause(vec![!y, !x1, !x2]);
+                    cnf.add_clause(vec![y, !x1, x2]);
+                    cnf.add_clause(vec![y, x1, !x2]);
+                    y
+                }
+            };
+            lits.insert(Bit(index), lit);
+        }
+
+        for root in roots {
+            cnf.add_clause(vec![lits[root]]);
+        }
+
+        (cnf, inputs)
+    }
+
+    /// indices of all nodes the roots depend on, children before parents
+    fn reachable(&self, roots: &[Bit]) -> BTreeSet<usize> {
+        let mut reachable = BTreeSet::new();
+        let mut stack: Vec<Bit> = roots.to_vec();
+        while let Some(bit) = stack.pop() {
+            if !reachable.insert(bit.0) {
+                continue;
+            }
+            match self.nodes[bit.0] {
+                Node::Constant(_) | Node::Input(_) => {}
+                Node::Not(x) => stack.push(x),
+                Node::And(x, y) | Node::Or(x, y) | Node::Xor(x, y) => stack.extend([x, y]),
+            }
+        }
+        reachable
+    }
+
+    fn name(&self, bit: Bit) -> String {
+        match self.nodes[bit.0] {
+            Node::Constant(value) => u8::from(value).to_string(),
+            Node::Input(i) => format!("a{}", i),
+            _ => format!("t{}", bit.0),
+        }
+    }
+}
+
+/// Path through the program, taken if all conditions hold
+pub struct SymbolicPath {
+    pub conditions: Vec<Bit>,
+    /// output values, lowest bit first
+    pub outputs: Vec<[Bit; 3]>,
+}
+
+pub struct Exploration {
+    pub circuit: Circuit,
+    pub paths: Vec<SymbolicPath>,
+}
+
+impl Display for Exploration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let roots: Vec<Bit> = self
+            .paths
+            .iter()
+            .flat_map(|path| path.conditions.iter().chain(path.outputs.iter().flatten()))
+            .copied()
+            .collect();
+        for index in self.circuit.reachable(&roots) {
+            let bit = Bit(index);
+            let name = |x| self.circuit.name(x);
+            match self.circuit.nodes[index] {
+                Node::Constant(_) | Node::Input(_) => continue,
+                Node::Not(x) => writeln!(f, "{} = !{}", name(bit), name(x))?,
+                Node::And(x, y) => writeln!(f, "{} = {} & {}", name(bit), name(x), name(y))?,
+                Node::Or(x, y) => writeln!(f, "{} = {} | {}", name(bit), name(x), name(y))?,
+                Node::Xor(x, y) => writeln!(f, "{} = {} ^ {}", name(bit), name(x), name(y))?,
+            }
+        }
+
+        for (n, path) in self.paths.iter().enumerate() {
+            let conditions: Vec<String> = path
+                .conditions
+                .iter()
+                .map(|c| self.circuit.name(*c))
+                .collect();
+            writeln!(f, "path {} if [{}]:", n, conditions.join(", "))?;
+            for (i, out) in path.outputs.iter().enumerate() {
+                let bits: Vec<String> = out.iter().rev().map(|b| self.circuit.name(*b)).collect();
+                writeln!(f, "  out {} = [{}]", i, bits.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct SymbolicState {
+    pc: usize,
+    a: Word,
+    b: Word,
+    c: Word,
+    d: Word,
+    outputs: Vec<[Bit; 3]>,
+    conditions: Vec<Bit>,
+}
+
+/// Executes the program with the lowest `input_bits` of register A unknown,
+/// forking at every jnz that depends on them. If a target is given, only paths
+/// producing exactly the target are kept. Paths on which the interpreter would
+/// fail with an execution error are dropped.
+pub fn explore(
+    program: &Program,
+    input_bits: usize,
+    target: Option<&[u8]>,
+    max_outputs: usize,
+) -> Result<Exploration> {
+    let mut circuit = Circuit::new();
+    let a = std::array::from_fn(|i| {
+        if i < input_bits {
+            circuit.input(i)
+        } else {
+            FALSE
+        }
+    });
+    let zero = circuit.word_constant(0);
+    let mut stack = vec![SymbolicState {
+        pc: 0,
+        a,
+        b: zero,
+        c: zero,
+        d: zero,
+        outputs: Vec::new(),
+        conditions: Vec::new(),
+    }];
+    let mut paths = Vec::new();
+    let mut steps = 0;
+
+    while let Some(mut state) = stack.pop() {
+        if !state.pc.is_multiple_of(2) {
+            continue;
+        }
+        let Some(instruction) = program.get_instruction(state.pc) else {
+            if target.is_none_or(|target| target.len() == state.outputs.len()) {
+                paths.push(SymbolicPath {
+                    conditions: state.conditions,
+                    outputs: state.outputs,
+                });
+            }
+            continue;
+        };
+        steps += 1;
+        if steps > MAX_STEPS {
+            bail!("Symbolic execution exceeded {} steps", MAX_STEPS);
+        }
+
+        state.pc += 2;
+        match instruction {
+            Instruction::Adv(operand) => {
+                let Some(shift) = combo(&state, operand, &circuit) else {
+                    continue;
+                };
+                let in_range = circuit.shift_in_range(&shift);
+                if !add_condition(&mut state, in_range) {
+                    continue;
+                }
+                state.a = circuit.word_shift_right(&state.a, &shift);
+            }
+            Instruction::Bxl(Literal(n)) => {
+                let n = circuit.word_constant(u64::from(*n));
+                state.b = circuit.word_xor(&state.b, &n);
+            }
+            Instruction::Bst(operand) => {
+                let Some(value) = combo(&state, operand, &circuit) else {
+                    continue;
+                };
+                state.b = circuit.word_mod_8(&value);
+            }
+            Instruction::Jnz(Literal(n)) => {
+                let jump = circuit.word_is_nonzero(&state.a);
+                let mut jumped = state.clone();
+                jumped.pc = usize::from(*n);
+                if add_condition(&mut jumped, jump) {
+                    stack.push(jumped);
+                }
+                let no_jump = circuit.not(jump);
+                if !add_condition(&mut state, no_jump) {
+                    continue;
+                }
+            }
+            Instruction::Bxc(_) => {
+                state.b = circuit.word_xor(&state.b, &state.c);
+            }
+            Instruction::Out(operand) => {
+                let Some(value) = combo(&state, operand, &circuit) else {
+                    continue;
+                };
+                let out = [value[0], value[1], value[2]];
+                if let Some(expected) = target.and_then(|t| t.get(state.outputs.len())) {
+                    let equal = circuit.bits_equal_constant(&out, *expected);
+                    if !add_condition(&mut state, equal) {
+                        continue;
+                    }
+                }
+                state.outputs.push(out);
+                let limit = target.map_or(max_outputs, |t| t.len());
+                if state.outputs.len() > limit {
+                    continue;
+                }
+            }
+            Instruction::Bdv(operand) => {
+                let Some(shift) = combo(&state, operand, &circuit) else {
+                    continue;
+                };
+                let in_range = circuit.shift_in_range(&shift);
+                if !add_condition(&mut state, in_range) {
+                    continue;
+                }
+                state.b = circuit.word_shift_right(&state.a, &shift);
+            }
+            Instruction::Cdv(operand) => {
+                let Some(shift) = combo(&state, operand, &circuit) else {
+                    continue;
+                };
+                let in_range = circuit.shift_in_range(&shift);
+                if !add_condition(&mut state, in_range) {
+                    continue;
+                }
+                state.c = circuit.word_shift_right(&state.a, &shift);
+            }
+            Instruction::Extended(..) => {
+                bail!("Symbolic execution does not support {}", instruction)
+            }
+        }
+        stack.push(state);
+    }
+
+    Ok(Exploration { circuit, paths })
+}
+
+/// Finds the minimal register A producing `target` by bit-blasting every
+/// symbolic path and asking the SAT solver for the smallest input.
+pub fn solve(program: &Program, target: &[u8]) -> Result<u64> {
+    let exploration = explore(program, WORD_SIZE, Some(target), target.len())?;
+    let mut best: Option<u64> = None;
+
+    for path in &exploration.paths {
+        let (cnf, inputs) = exploration.circuit.to_cnf(&path.conditions);
+        // deciding the highest bits first, with 0 before 1, yields the minimal a
+        let order: Vec<usize> = (0..WORD_SIZE)
+            .rev()
+            .filter_map(|i| inputs.get(&i).copied())
+            .collect();
+        if let Some(model) = sat::solve(&cnf, &order) {
+            let a = inputs
+                .iter()
+                .filter(|(_, variable)| model[**variable])
+                .fold(0, |a, (i, _)| a | (1 << i));
+            best = Some(best.map_or(a, |best| best.min(a)));
+        }
+    }
+
+    best.ok_or(SolveError::NoSolution.into())
+}
+
+/// returns false if the path became infeasible
+fn add_condition(state: &mut SymbolicState, condition: Bit) -> bool {
+    match condition {
+        FALSE => false,
+        TRUE => true,
+        _ => {
+            state.conditions.push(condition);
+            true
+        }
+    }
+}
+
+/// returns None for the reserved operand, which fails when executed
+fn combo(state: &SymbolicState, operand: &Combo, circuit: &Circuit) -> Option<Word> {
+    match operand {
+        Combo::Literal(n) => Some(circuit.word_constant(u64::from(*n))),
+        Combo::Register(Register::A) => Some(state.a),
+        Combo::Register(Register::B) => Some(state.b),
+        Combo::Register(Register::C) => Some(state.c),
+        Combo::Register(Register::D) => Some(state.d),
+        Combo::Reserved => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::run_with_register_a, parse::program, solver};
+
+    use super::*;
+
+    #[test]
+    fn symbolic_outputs_match_concrete_execution() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+        let exploration = explore(&program, 12, None, 16)?;
+
+        for a in 0..(1 << 12) {
+            let values = exploration.circuit.evaluate(a);
+            let path = exploration
+                .paths
+                .iter()
+                .find(|path| path.conditions.iter().all(|c| values[c.0]))
+                .expect("Every input takes one path");
+            let out: Vec<u8> = path
+                .outputs
+                .iter()
+                .map(|bits| {
+                    bits.iter()
+                        .enumerate()
+                        .map(|(i, b)| u8::from(values[b.0]) << i)
+                        .sum()
+                })
+                .collect();
+
+            assert_eq!(run_with_register_a(&program, a)?, out);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn drops_paths_that_fail_to_execute() -> Result<()> {
+        // adv(A), out(A) overflows for a >= 64
+        let program = program("0,4,5,4")?;
+        let exploration = explore(&program, 7, None, 1)?;
+
+        for a in 0..(1 << 7) {
+            let values = exploration.circuit.evaluate(a);
+            let has_path = exploration
+                .paths
+                .iter()
+                .any(|path| path.conditions.iter().all(|c| values[c.0]));
+
+            assert_eq!(run_with_register_a(&program, a).is_ok(), has_path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn solves_quine() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        assert_eq!(117440, solve(&program, &program.encode())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn agrees_with_structural_solver() -> Result<()> {
+        let program = program("2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0")?;
+
+        for a in [5, 100, 1234, 4321, 987654321] {
+            let target = run_with_register_a(&program, a)?;
+
+            assert_eq!(solver::solve(&program, &target)?, solve(&program, &target)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn solves_programs_outside_the_loop_shape() -> Result<()> {
+        // outputs twice per iteration, which the structural solver rejects
+        let program = program("0,1,5,4,5,4,3,0")?;
+        let target = run_with_register_a(&program, 37)?;
+
+        let a = solve(&program, &target)?;
+
+        assert_eq!(target, run_with_register_a(&program, a)?);
+        assert!((0..a).all(|a| run_with_register_a(&program, a).unwrap() != target));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_missing_solution() -> Result<()> {
+        let program = program("0,3,5,4,3,0")?;
+
+        let error = solve(&program, &[1]).expect_err("There is no solution");
+
+        assert!(matches!(
+            error.downcast::<SolveError>()?,
+            SolveError::NoSolution
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::state::State;
+
+/// Expression over the registers and the pc, e.g. `a % 8` or `(a >> 3) ^ b`.
+/// Evaluated with wrapping arithmetic, so watching never aborts a debugging session.
+pub struct Watch {
+    source: String,
+    expression: Expression,
+}
+
+impl Watch {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expression = parser.parse_expression(0)?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            bail!("Unexpected token {} in watch expression", token);
+        }
+
+        Ok(Watch {
+            source: source.trim().to_string(),
+            expression,
+        })
+    }
+
+    pub fn evaluate(&self, state: &State) -> Option<u64> {
+        self.expression.evaluate(state)
+    }
+}
+
+impl Display for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+enum Expression {
+    Number(u64),
+    Variable(Variable),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+impl Expression {
+    /// returns None on division by zero
+    fn evaluate(&self, state: &State) -> Option<u64> {
+        match self {
+            Expression::Number(n) => Some(*n),
+            Expression::Variable(Variable::A) => Some(state.registers.a),
+            Expression::Variable(Variable::B) => Some(state.registers.b),
+            Expression::Variable(Variable::C) => Some(state.registers.c),
+            Expression::Variable(Variable::D) => Some(state.registers.d),
+            Expression::Variable(Variable::Pc) => state.pc.try_into().ok(),
+            Expression::Binary(left, operator, right) => {
+                let left = left.evaluate(state)?;
+                let right = right.evaluate(state)?;
+                operator.apply(left, right)
+            }
+        }
+    }
+}
+
+enum Variable {
+    A,
+    B,
+    C,
+    D,
+    Pc,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Or,
+    Xor,
+    And,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl Operator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::Xor => 2,
+            Operator::And => 3,
+            Operator::ShiftLeft | Operator::ShiftRight => 4,
+            Operator::Add | Operator::Subtract => 5,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 6,
+        }
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        let shift = u32::try_from(right).unwrap_or(u32::MAX);
+        match self {
+            Operator::Or => Some(left | right),
+            Operator::Xor => Some(left ^ right),
+            Operator::And => Some(left & right),
+            Operator::ShiftLeft => Some(left.checked_shl(shift).unwrap_or(0)),
+            Operator::ShiftRight => Some(left.checked_shr(shift).unwrap_or(0)),
+            Operator::Add => Some(left.wrapping_add(right)),
+            Operator::Subtract => Some(left.wrapping_sub(right)),
+            Operator::Multiply => Some(left.wrapping_mul(right)),
+            Operator::Divide => left.checked_div(right),
+            Operator::Modulo => left.checked_rem(right),
+        }
+    }
+}
+
+enum Token {
+    Number(u64),
+    Identifier(String),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(identifier) => write!(f, "{}", identifier),
+            Token::Operator(_) => write!(f, "operator"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' => continue,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '|' => Token::Operator(Operator::Or),
+            '^' => Token::Operator(Operator::Xor),
+            '&' => Token::Operator(Operator::And),
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '%' => Token::Operator(Operator::Modulo),
+            '<' | '>' => {
+                if chars.next_if_eq(&c).is_none() {
+                    bail!("Expected {}{} in watch expression", c, c);
+                }
+                if c == '<' {
+                    Token::Operator(Operator::ShiftLeft)
+                } else {
+                    Token::Operator(Operator::ShiftRight)
+                }
+            }
+            '0'..='9' => {
+                let mut digits = c.to_string();
+                while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                }
+                Token::Number(digits.parse()?)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut identifier = c.to_string();
+                while let Some(d) = chars.next_if(|d| d.is_ascii_alphabetic()) {
+                    identifier.push(d);
+                }
+                Token::Identifier(identifier.to_lowercase())
+            }
+            _ => bail!("Unexpected character '{}' in watch expression", c),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    /// precedence climbing, all operators are left-associative
+    fn parse_expression(&mut self, min_precedence: u8) -> Result<Expression> {
+        let mut left = self.parse_term()?;
+
+        while let Some(Token::Operator(operator)) = self.tokens.get(self.position) {
+            let operator = *operator;
+            if operator.precedence() <= min_precedence {
+                break;
+            }
+            self.position += 1;
+            let right = self.parse_expression(operator.precedence())?;
+            left = Expression::Binary(Box::new(left), operator, Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_term(&mut self) -> Result<Expression> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or(anyhow!("Unexpected end of watch expression"))?;
+        self.position += 1;
+
+        match token {
+            Token::Number(n) => Ok(Expression::Number(*n)),
+            Token::Identifier(identifier) => match identifier.as_str() {
+                "a" => Ok(Expression::Variable(Variable::A)),
+                "b" => Ok(Expression::Variable(Variable::B)),
+                "c" => Ok(Expression::Variable(Variable::C)),
+                "d" => Ok(Expression::Variable(Variable::D)),
+                "pc" => Ok(Expression::Variable(Variable::Pc)),
+                _ => Err(anyhow!(
+                    "Unknown variable {} in watch expression",
+                    identifier
+                )),
+            },
+            Token::OpenParen => {
+                let expression = self.parse_expression(0)?;
+                match self.tokens.get(self.position) {
+                    Some(Token::CloseParen) => {
+                        self.position += 1;
+                        Ok(expression)
+                    }
+                    _ => Err(anyhow!("Expected ) in watch expression")),
+                }
+            }
+            Token::Operator(_) | Token::CloseParen => {
+                Err(anyhow!("Unexpected token {} in watch expression", token))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::RegisterState;
+
+    use super::*;
+
+    fn state(a: u64, b: u64, c: u64) -> State {
+        State {
+            pc: 4,
+            registers: RegisterState { a, b, c, d: 40 },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evaluates_registers() -> Result<()> {
+        let state = state(10, 20, 30);
+
+        assert_eq!(Some(10), Watch::parse("a")?.evaluate(&state));
+        assert_eq!(Some(20), Watch::parse("B")?.evaluate(&state));
+        assert_eq!(Some(30), Watch::parse("c")?.evaluate(&state));
+        assert_eq!(Some(40), Watch::parse("d")?.evaluate(&state));
+        assert_eq!(Some(4), Watch::parse("pc")?.evaluate(&state));
+
+        Ok(())
+    }
+
+    #[test]
+    fn respects_precedence() -> Result<()> {
+        let state = state(0b101101, 3, 0);
+
+        assert_eq!(Some(7), Watch::parse("1 + 2 * 3")?.evaluate(&state));
+        assert_eq!(Some(9), Watch::parse("(1 + 2) * 3")?.evaluate(&state));
+        assert_eq!(
+            Some(0b101 ^ 3),
+            Watch::parse("a >> 3 ^ b")?.evaluate(&state)
+        );
+        assert_eq!(Some(0b101), Watch::parse("a % 8")?.evaluate(&state));
+        assert_eq!(Some(1), Watch::parse("10 - 6 - 3")?.evaluate(&state));
+
+        Ok(())
+    }
+
+    #[test]
+    fn division_by_zero_has_no_value() -> Result<()> {
+        assert_eq!(None, Watch::parse("a / c")?.evaluate(&state(1, 0, 0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(Watch::parse("a +").is_err());
+        assert!(Watch::parse("(a").is_err());
+        assert!(Watch::parse("e").is_err());
+        assert!(Watch::parse("a > 3").is_err());
+        assert!(Watch::parse("a b").is_err());
+    }
+}